@@ -0,0 +1,301 @@
+// which tests have both files, are numbered without gaps and differ from each other
+
+use crate::sanitize::content_hash;
+use colored::*;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Inputs and answers under a directory, told apart and matched by a part of their names:
+/// `1.in` with `1.out`, or `input_1.txt` with `output_1.txt`.
+pub struct Pairing {
+	pub pairs: Vec<(PathBuf, PathBuf)>,
+	pub orphan_inputs: Vec<PathBuf>,
+	pub orphan_answers: Vec<PathBuf>,
+}
+
+// the path with `part` of its name replaced, the same for an input and its answer
+fn key(path: &Path, part: &str) -> Option<PathBuf> {
+	let name = path.file_name()?.to_str()?;
+	if !name.contains(part) {
+		return None;
+	}
+	Some(path.with_file_name(name.replacen(part, "*", 1)))
+}
+
+/// Files with `input` in their name are inputs, else with `answer` answers. Sorted by name.
+pub fn pair(dir: &Path, input: &str, answer: &str) -> Result<Pairing, Box<dyn Error>> {
+	let mut inputs = BTreeMap::new();
+	let mut answers = BTreeMap::new();
+	for entry in WalkDir::new(dir).sort_by_file_name() {
+		let entry = entry?;
+		if !entry.file_type().is_file() {
+			continue;
+		}
+		let path = entry.into_path();
+		if let Some(key) = key(&path, input) {
+			inputs.insert(key, path);
+		} else if let Some(key) = key(&path, answer) {
+			answers.insert(key, path);
+		}
+	}
+
+	let mut pairing = Pairing {
+		pairs: Vec::new(),
+		orphan_inputs: Vec::new(),
+		orphan_answers: Vec::new(),
+	};
+	for (key, input) in inputs {
+		match answers.remove(&key) {
+			Some(answer) => pairing.pairs.push((input, answer)),
+			None => pairing.orphan_inputs.push(input),
+		}
+	}
+	pairing.orphan_answers = answers.into_values().collect();
+	Ok(pairing)
+}
+
+// a longer run of missing numbers ends the series, the names past it (dates, seeds) are not counted
+const MAX_GAP: u64 = 1000;
+
+/// Ranges of numbers missing from each series of names that differ only in their last number,
+/// `1.in 2.in 5.in` is `#.in` missing `(3, 4)`. A series starts at 0 or 1 and ends before a gap
+/// wider than `MAX_GAP`.
+pub fn gaps(files: &[PathBuf]) -> Vec<(PathBuf, Vec<(u64, u64)>)> {
+	let mut series: BTreeMap<PathBuf, Vec<u64>> = BTreeMap::new();
+	for path in files {
+		let name = match path.file_name().and_then(|s| s.to_str()) {
+			Some(name) => name,
+			None => continue,
+		};
+		let end = match name.rfind(|c: char| c.is_ascii_digit()) {
+			Some(i) => i + 1,
+			None => continue,
+		};
+		let start = name[..end]
+			.rfind(|c: char| !c.is_ascii_digit())
+			.map_or(0, |i| i + 1);
+		if let Ok(n) = name[start..end].parse() {
+			let pattern = format!("{}#{}", &name[..start], &name[end..]);
+			series
+				.entry(path.with_file_name(pattern))
+				.or_default()
+				.push(n);
+		}
+	}
+
+	let mut gaps = Vec::new();
+	for (pattern, mut numbers) in series {
+		numbers.sort_unstable();
+		numbers.dedup();
+		let mut next = std::cmp::min(numbers[0], 1);
+		let mut missing = Vec::new();
+		for n in numbers {
+			if n - next > MAX_GAP {
+				break;
+			}
+			if n > next {
+				missing.push((next, n - 1));
+			}
+			next = n + 1;
+		}
+		if !missing.is_empty() {
+			gaps.push((pattern, missing));
+		}
+	}
+	gaps
+}
+
+// 3, 5-7
+fn ranges(ranges: &[(u64, u64)]) -> String {
+	let parts: Vec<String> = ranges
+		.iter()
+		.map(|&(a, b)| {
+			if a == b {
+				a.to_string()
+			} else {
+				format!("{}-{}", a, b)
+			}
+		})
+		.collect();
+	parts.join(", ")
+}
+
+pub struct Duplicate {
+	pub path: PathBuf,
+	// the first file with the same content
+	pub same_as: PathBuf,
+	// byte-identical, else only the same tokens
+	pub exact: bool,
+}
+
+// the tokens joined by single spaces
+fn tokens(data: &[u8]) -> Vec<u8> {
+	let mut s = Vec::with_capacity(data.len());
+	for token in data
+		.split(|b| b.is_ascii_whitespace())
+		.filter(|t| !t.is_empty())
+	{
+		if !s.is_empty() {
+			s.push(b' ');
+		}
+		s.extend_from_slice(token);
+	}
+	s
+}
+
+// the first of `candidates` whose content, after `key`, is `data`
+fn same_content<'a, F: Fn(&[u8]) -> Vec<u8>>(
+	candidates: &[&'a PathBuf],
+	data: &[u8],
+	key: F,
+) -> std::io::Result<Option<&'a PathBuf>> {
+	for &path in candidates {
+		if key(&std::fs::read(path)?) == data {
+			return Ok(Some(path));
+		}
+	}
+	Ok(None)
+}
+
+/// Files with the same bytes, or the same tokens, as an earlier one. Files with the same content
+/// hash are compared byte by byte.
+pub fn duplicates(files: &[PathBuf]) -> std::io::Result<Vec<Duplicate>> {
+	// the first file of each distinct content, by hash
+	let mut exact: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+	let mut loose: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+	let mut duplicates = Vec::new();
+	for path in files {
+		let data = std::fs::read(path)?;
+		let loose_data = tokens(&data);
+		let exact_seen = exact.entry(content_hash(&data)).or_default();
+		let loose_seen = loose.entry(content_hash(&loose_data)).or_default();
+		if let Some(first) = same_content(exact_seen, &data, |d| d.to_vec())? {
+			duplicates.push(Duplicate {
+				path: path.clone(),
+				same_as: first.to_path_buf(),
+				exact: true,
+			});
+			continue;
+		}
+		exact_seen.push(path);
+		match same_content(loose_seen, &loose_data, tokens)? {
+			Some(first) => duplicates.push(Duplicate {
+				path: path.clone(),
+				same_as: first.to_path_buf(),
+				exact: false,
+			}),
+			None => loose_seen.push(path),
+		}
+	}
+	Ok(duplicates)
+}
+
+/// Prints every problem found under `dir` and returns how many.
+pub fn audit(dir: &Path, input: &str, answer: &str) -> Result<usize, Box<dyn Error>> {
+	println!("{}", "Auditing ..".green());
+	let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).display().to_string();
+	let report = |message: &str, path: &Path| {
+		eprintln!("{} {}", "[Error]".red(), message);
+		eprintln!("\t=> {}", relative(path));
+	};
+
+	let pairing = pair(dir, input, answer)?;
+	for path in &pairing.orphan_inputs {
+		report("no answer", path);
+	}
+	for path in &pairing.orphan_answers {
+		report("no input", path);
+	}
+
+	let inputs: Vec<PathBuf> = {
+		let mut v: Vec<PathBuf> = pairing.pairs.iter().map(|(i, _)| i.clone()).collect();
+		v.extend(pairing.orphan_inputs.iter().cloned());
+		v.sort();
+		v
+	};
+	let gaps = gaps(&inputs);
+	for (pattern, missing) in &gaps {
+		report(&format!("missing {}", ranges(missing)), pattern);
+	}
+
+	let duplicates = duplicates(&inputs)?;
+	for d in &duplicates {
+		let message = format!(
+			"same as {}{}",
+			relative(&d.same_as),
+			if d.exact { "" } else { " up to whitespace" }
+		);
+		report(&message, &d.path);
+	}
+
+	let orphans = pairing.orphan_inputs.len() + pairing.orphan_answers.len();
+	let missing: u64 = gaps
+		.iter()
+		.flat_map(|(_, m)| m.iter().map(|&(a, b)| b - a + 1))
+		.sum();
+	println!();
+	println!("      {}: {}", "Tests".green(), pairing.pairs.len());
+	println!("    {}: {}", "Orphans".red(), orphans);
+	println!("       {}: {}", "Gaps".red(), missing);
+	println!(" {}: {}", "Duplicates".red(), duplicates.len());
+	Ok(orphans + missing as usize + duplicates.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn paths(names: &[&str]) -> Vec<PathBuf> {
+		names.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn missing_ranges() {
+		let files = paths(&["d/1.in", "d/2.in", "d/5.in", "d/9.in", "d/x.in"]);
+		assert_eq!(
+			gaps(&files),
+			[(PathBuf::from("d/#.in"), vec![(3, 4), (6, 8)])]
+		);
+		assert_eq!(ranges(&gaps(&files)[0].1), "3-4, 6-8");
+		// a series may start at 0, each one is on its own
+		let files = paths(&["0.in", "1.in", "a1.in", "a3.in"]);
+		assert_eq!(gaps(&files), [(PathBuf::from("a#.in"), vec![(2, 2)])]);
+		assert!(gaps(&paths(&["1.in", "2.in", "3.in"])).is_empty());
+	}
+
+	#[test]
+	fn outliers_end_the_series() {
+		assert!(gaps(&paths(&["20240101.in"])).is_empty());
+		let files = paths(&["1.in", "3.in", "20240101.in", "20240103.in"]);
+		assert_eq!(gaps(&files), [(PathBuf::from("#.in"), vec![(2, 2)])]);
+		let files = paths(&["1.in", &format!("{}.in", u64::MAX)]);
+		assert!(gaps(&files).is_empty());
+	}
+
+	#[test]
+	fn same_bytes_or_tokens() {
+		let dir = tempfile::tempdir().unwrap();
+		let write = |name: &str, data: &str| {
+			let path = dir.path().join(name);
+			std::fs::write(&path, data).unwrap();
+			path
+		};
+		let files = vec![
+			write("1.in", "1 2\n"),
+			write("2.in", "1 2\n"),
+			write("3.in", "1  2\r\n"),
+			write("4.in", "1 3\n"),
+		];
+		let found = duplicates(&files).unwrap();
+		let found: Vec<_> = found
+			.iter()
+			.map(|d| (&d.path, &d.same_as, d.exact))
+			.collect();
+		assert_eq!(
+			found,
+			[(&files[1], &files[0], true), (&files[2], &files[0], false)]
+		);
+	}
+}
@@ -1,5 +1,10 @@
 use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
 use indoc::indoc;
+use ps_util::interval::INTERVAL_MODES;
+use ps_util::matrix::MATRIX_MODES;
+use ps_util::number::NUMBER_MODES;
+use ps_util::sanitize::RULE_NAMES;
+use ps_util::weight::WEIGHT_DISTS;
 
 pub fn build_cli() -> App<'static> {
     App::new("psutil")
@@ -7,31 +12,202 @@ pub fn build_cli() -> App<'static> {
         .author("github.com/elbaro/psutil")
         .about("data util for algo ps")
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        // .cpp validators, checkers and solutions are compiled with these
+        .arg(
+            Arg::with_name("cxx")
+                .long("cxx")
+                .takes_value(true)
+                .global(true)
+                .help("C++ compiler for .cpp sources, $CXX or g++ by default"),
+        )
+        .arg(
+            Arg::with_name("cxxflags")
+                .long("cxxflags")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .global(true)
+                .help("Compiler flags, $CXXFLAGS or \"-O2 -std=c++17\" by default"),
+        )
         .subcommand(
             SubCommand::with_name("generate")
                 .setting(AppSettings::SubcommandRequiredElseHelp)
+                // psutil generate graph 100000 200000 -i 1 1000000000 --max-bytes 1000000 --shrink
+                .arg(
+                    Arg::with_name("max-bytes")
+                        .long("max-bytes")
+                        .takes_value(true)
+                        .global(true)
+                        .help("Fail if the output is larger than this"),
+                )
+                .arg(
+                    Arg::with_name("shrink")
+                        .long("shrink")
+                        .requires("max-bytes")
+                        .global(true)
+                        .help("Shrink n, m or the weight digits until the output fits --max-bytes"),
+                )
                 .subcommand(
                     SubCommand::with_name("tree")
                         .arg(Arg::with_name("n").required(true).index(1))
-                        .arg(Arg::with_name("int-weight").short('i').number_of_values(2))
+                        .arg(
+                            Arg::with_name("int-weight")
+                                .short('i')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
+                        .arg(
+                            Arg::with_name("float-weight")
+                                .short('f')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
+                        .group(ArgGroup::with_name("weight").args(&["int-weight", "float-weight"]))
+                        .arg(
+                            Arg::with_name("dist")
+                                .long("dist")
+                                .takes_value(true)
+                                .possible_values(WEIGHT_DISTS)
+                                .requires("weight"),
+                        )
+                        .arg(Arg::with_name("directed").long("directed"))
+                        .arg(
+                            Arg::with_name("diameter")
+                                .long("diameter")
+                                .takes_value(true)
+                                .conflicts_with("min-diameter"),
+                        )
+                        .arg(
+                            Arg::with_name("min-diameter")
+                                .long("min-diameter")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("max-degree")
+                                .long("max-degree")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("min-leaves")
+                                .long("min-leaves")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("graph")
+                        .arg(Arg::with_name("n").required(true).index(1))
+                        .arg(Arg::with_name("m").required(true).index(2))
+                        .arg(
+                            Arg::with_name("int-weight")
+                                .short('i')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
                         .arg(
                             Arg::with_name("float-weight")
                                 .short('f')
-                                .number_of_values(2),
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
                         )
                         .group(ArgGroup::with_name("weight").args(&["int-weight", "float-weight"]))
+                        .arg(
+                            Arg::with_name("dist")
+                                .long("dist")
+                                .takes_value(true)
+                                .possible_values(WEIGHT_DISTS)
+                                .requires("weight"),
+                        )
+                        .arg(Arg::with_name("connected").long("connected"))
                         .arg(Arg::with_name("directed").long("directed")),
                 )
                 .subcommand(
                     SubCommand::with_name("convex")
                         .arg(Arg::with_name("n").required(true).index(1))
-                        .arg(Arg::with_name("int-range").short('i').number_of_values(2))
-                        .arg(Arg::with_name("float-range").short('f').number_of_values(2))
+                        .arg(
+                            Arg::with_name("int-range")
+                                .short('i')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
+                        .arg(
+                            Arg::with_name("float-range")
+                                .short('f')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
                         .group(
                             ArgGroup::with_name("range")
                                 .args(&["int-range", "float-range"])
                                 .required(true),
                         ),
+                )
+                .subcommand(
+                    // psutil generate numbers strong-pseudoprimes 5 1000000000 --bases 2,3
+                    SubCommand::with_name("numbers")
+                        .arg(
+                            Arg::with_name("mode")
+                                .required(true)
+                                .index(1)
+                                .possible_values(NUMBER_MODES),
+                        )
+                        .arg(Arg::with_name("count").required(true).index(2))
+                        .arg(Arg::with_name("bound").required(true).index(3))
+                        .arg(
+                            Arg::with_name("bases")
+                                .long("bases")
+                                .takes_value(true)
+                                .multiple(true)
+                                .use_delimiter(true),
+                        ),
+                )
+                .subcommand(
+                    // psutil generate matrix 5 5 --mode rank --rank 3 --mod 998244353
+                    SubCommand::with_name("matrix")
+                        .arg(Arg::with_name("r").required(true).index(1))
+                        .arg(Arg::with_name("c").required(true).index(2))
+                        .arg(
+                            Arg::with_name("mode")
+                                .long("mode")
+                                .takes_value(true)
+                                .default_value("random")
+                                .possible_values(MATRIX_MODES),
+                        )
+                        .arg(
+                            Arg::with_name("int-range")
+                                .short('i')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true),
+                        )
+                        .arg(Arg::with_name("rank").long("rank").takes_value(true))
+                        .arg(Arg::with_name("mod").long("mod").takes_value(true))
+                        .arg(Arg::with_name("edges").long("edges").takes_value(true))
+                        .arg(Arg::with_name("connected").long("connected"))
+                        .arg(Arg::with_name("directed").long("directed")),
+                )
+                .subcommand(
+                    // psutil generate intervals 100 -i 1 1000000000 --mode laminar
+                    SubCommand::with_name("intervals")
+                        .arg(Arg::with_name("n").required(true).index(1))
+                        .arg(
+                            Arg::with_name("int-range")
+                                .short('i')
+                                .number_of_values(2)
+                                .allow_hyphen_values(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("mode")
+                                .long("mode")
+                                .takes_value(true)
+                                .default_value("random")
+                                .possible_values(INTERVAL_MODES),
+                        ),
+                )
+                .subcommand(
+                    // psutil generate script gen.rhai --seed 42 -- 10 1000
+                    SubCommand::with_name("script")
+                        .arg(Arg::with_name("path").required(true).index(1))
+                        .arg(Arg::with_name("seed").long("seed").takes_value(true))
+                        .arg(Arg::with_name("args").index(2).multiple(true).last(true)),
                 ),
         )
         .subcommand(
@@ -47,12 +223,128 @@ pub fn build_cli() -> App<'static> {
                         .required(true),
                 )
                 .arg(Arg::with_name("confirmed").long("confirmed"))
-                .about("psutil sanitize data/A --ext txt,in,out"),
+                .arg(
+                    Arg::with_name("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .takes_value(true)
+                        .help("Files checked in parallel, one per CPU by default"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .default_value("text")
+                        .possible_values(["text", "json"]),
+                )
+                .arg(
+                    Arg::with_name("no-cache")
+                        .long("no-cache")
+                        .help("Check every file, even if it passed last time"),
+                )
+                .arg(
+                    Arg::with_name("backup")
+                        .long("backup")
+                        .takes_value(true)
+                        .help("Copy originals here before rewriting them"),
+                )
+                .arg(
+                    Arg::with_name("utf8")
+                        .long("utf8")
+                        .help("Accept valid UTF-8 instead of ASCII and check confusable characters"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .long("config")
+                        .takes_value(true)
+                        .help("Rule file with `rule = off | check | fix` lines"),
+                )
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .possible_values(RULE_NAMES),
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .possible_values(RULE_NAMES),
+                )
+                .arg(
+                    Arg::with_name("skip")
+                        .long("skip")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .possible_values(RULE_NAMES),
+                )
+                .about("psutil sanitize data/A --ext txt,in,out --fix trailing-space --check double-space"),
+        )
+        .subcommand(
+            SubCommand::with_name("tests")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    // psutil tests audit data/A --in .in --out .out
+                    SubCommand::with_name("audit")
+                        .about("Report unpaired, missing and duplicate tests")
+                        .arg(Arg::with_name("dir").index(1).required(true))
+                        .arg(
+                            Arg::with_name("in")
+                                .long("in")
+                                .takes_value(true)
+                                .default_value(".in"),
+                        )
+                        .arg(
+                            Arg::with_name("out")
+                                .long("out")
+                                .takes_value(true)
+                                .default_value(".out"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("samples")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    // psutil samples check 1004H/problem.html --validator validator.cpp
+                    SubCommand::with_name("check")
+                        .about("Compare the samples of a statement (.html or .md) with the sample tests")
+                        .arg(Arg::with_name("statement").index(1).required(true))
+                        .arg(
+                            Arg::with_name("dir")
+                                .index(2)
+                                .help("Where the sample tests are, next to the statement by default"),
+                        )
+                        .arg(
+                            Arg::with_name("in")
+                                .long("in")
+                                .takes_value(true)
+                                .default_value("input"),
+                        )
+                        .arg(
+                            Arg::with_name("out")
+                                .long("out")
+                                .takes_value(true)
+                                .default_value("output"),
+                        )
+                        .arg(
+                            Arg::with_name("validator")
+                                .long("validator")
+                                .takes_value(true)
+                                .help("Also validate the sample inputs"),
+                        ),
+                ),
         )
         .subcommand(
             SubCommand::with_name("contest").about("Overview of upcoming or recent contests"),
         )
         .subcommand(
+            // psutil validate ./validator data/A --group 1 --args-file validate.cfg -- --max-n 1000
             SubCommand::with_name("validate")
                 .arg(Arg::with_name("validator").index(1).required(true))
                 .arg(
@@ -62,7 +354,68 @@ pub fn build_cli() -> App<'static> {
                         .takes_value(true)
                         .multiple(true),
                 )
-                .arg(Arg::with_name("filter").long("filter").takes_value(true)),
+                .arg(Arg::with_name("filter").long("filter").takes_value(true))
+                .arg(
+                    Arg::with_name("testset")
+                        .long("testset")
+                        .takes_value(true)
+                        .help("Passed to the validator as --testset"),
+                )
+                .arg(
+                    Arg::with_name("group")
+                        .long("group")
+                        .takes_value(true)
+                        .help("Passed to the validator as --group"),
+                )
+                .arg(
+                    Arg::with_name("args-file")
+                        .long("args-file")
+                        .takes_value(true)
+                        .help("`pattern = args` lines, for the files whose path matches the pattern"),
+                )
+                .arg(
+                    Arg::with_name("outputs")
+                        .long("outputs")
+                        .takes_value(true)
+                        // a testlib checker takes nothing after `input output answer`
+                        .conflicts_with_all(&["testset", "group", "args-file", "args"])
+                        .help("Check the outputs in this directory: run `validator input output answer` for each test"),
+                )
+                .arg(
+                    Arg::with_name("in")
+                        .long("in")
+                        .takes_value(true)
+                        .requires("outputs")
+                        .help("Part of the input names, .in by default"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .requires("outputs")
+                        .help("Part of the answer names, .out by default"),
+                )
+                .arg(
+                    Arg::with_name("coverage")
+                        .long("coverage")
+                        .conflicts_with("outputs")
+                        .help("Print which bounds and features the valid tests reach (testlib validators)"),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .takes_value(true)
+                        .help("Validator runs at once, one per CPU by default"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("Seconds before a validator is killed, 0 for no limit"),
+                )
+                .arg(Arg::with_name("args").index(3).multiple(true).last(true)),
         )
         .subcommand(
             // psutil eval ./a.out data/A/*.in data/B/*.out --time 0.5 --memory 64
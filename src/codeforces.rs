@@ -140,7 +140,7 @@ async fn login_async<'a>(_id: String, _pw: String) -> Result<(), failure::Error>
     // let mut driver: Driver = unsafe { std::mem::uninitialized() };
     // std::mem::swap(&mut session.driver, &mut driver);
 
-    let _cap = serde_json::from_str(
+    let _cap: serde_json::Value = serde_json::from_str(
         "{}", // 	r#"
              // 	{"moz:firefoxOptions":{"args":["-headless"]}}
              // "#,
@@ -0,0 +1,106 @@
+// C++ sources where an executable is expected, built once and cached by their hash
+
+use crate::sanitize::content_hash;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// available as `#include "testlib.h"` for every source
+const TESTLIB: &[u8] = include_bytes!("../examples/testlib.h");
+
+const SOURCE_EXTS: [&str; 3] = ["cpp", "cc", "cxx"];
+
+// lines of compiler output in an error
+const ERROR_LINES: usize = 20;
+
+pub struct Compiler {
+	pub cxx: String,
+	pub flags: Vec<String>,
+}
+
+impl Default for Compiler {
+	/// `$CXX` and `$CXXFLAGS`, else `g++ -O2 -std=c++17`.
+	fn default() -> Self {
+		let cxx = std::env::var("CXX").unwrap_or_else(|_| "g++".to_string());
+		let flags = match std::env::var("CXXFLAGS") {
+			Ok(flags) => flags.split_whitespace().map(String::from).collect(),
+			Err(_) => vec!["-O2".to_string(), "-std=c++17".to_string()],
+		};
+		Compiler { cxx, flags }
+	}
+}
+
+pub fn is_source(path: &Path) -> bool {
+	path.extension()
+		.and_then(|ext| ext.to_str())
+		.is_some_and(|ext| SOURCE_EXTS.contains(&ext))
+}
+
+/// `$XDG_CACHE_HOME/psutil`, `~/.cache/psutil` or a temporary directory.
+pub fn cache_dir() -> PathBuf {
+	std::env::var_os("XDG_CACHE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		.unwrap_or_else(std::env::temp_dir)
+		.join("psutil")
+}
+
+impl Compiler {
+	/// `path` itself unless it is a C++ source, else its binary, compiled if the source,
+	/// the compiler or the flags changed since the last time.
+	pub fn executable(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+		if !is_source(path) {
+			return Ok(path.to_path_buf());
+		}
+		let source = std::fs::read(path)?;
+		let key = {
+			let mut key = source.clone();
+			for part in std::iter::once(&self.cxx).chain(&self.flags) {
+				key.push(0);
+				key.extend_from_slice(part.as_bytes());
+			}
+			key.extend_from_slice(TESTLIB);
+			content_hash(&key)
+		};
+		let dir = cache_dir();
+		let stem = path.file_stem().unwrap().to_string_lossy();
+		let binary = dir.join(format!("{}-{:016x}", stem, key));
+		if binary.is_file() {
+			return Ok(binary);
+		}
+
+		let include = dir.join("include");
+		std::fs::create_dir_all(&include)?;
+		if std::fs::read(include.join("testlib.h")).ok().as_deref() != Some(TESTLIB) {
+			std::fs::write(include.join("testlib.h"), TESTLIB)?;
+		}
+		// renamed into place, two runs may compile the same source
+		let partial = dir.join(format!("{}-{:016x}.{}.tmp", stem, key, std::process::id()));
+		eprintln!("Compiling {} ..", path.display());
+		let output = Command::new(&self.cxx)
+			.args(&self.flags)
+			.arg("-I")
+			.arg(&include)
+			.arg("-o")
+			.arg(&partial)
+			.arg(path)
+			.output()
+			.map_err(|err| format!("cannot run {}: {}", self.cxx, err))?;
+		if !output.status.success() {
+			let _ = std::fs::remove_file(&partial);
+			let message = String::from_utf8_lossy(&output.stderr);
+			let lines: Vec<&str> = message.lines().collect();
+			let mut report = format!("{} failed", self.cxx);
+			for line in lines.iter().take(ERROR_LINES) {
+				report.push_str("\n\t");
+				report.push_str(line);
+			}
+			if lines.len() > ERROR_LINES {
+				report.push_str(&format!("\n\t.. {} more lines", lines.len() - ERROR_LINES));
+			}
+			return Err(report.into());
+		}
+		std::fs::rename(&partial, &binary)?;
+		Ok(binary)
+	}
+}
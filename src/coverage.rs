@@ -0,0 +1,134 @@
+// which bounds and features the tests reach, from testlib's --testOverviewLogFileName logs
+
+use colored::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// tests named per cell of the table
+const MAX_NAMED: usize = 3;
+
+#[derive(Default)]
+struct Bounds {
+	min: Vec<PathBuf>,
+	max: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Coverage {
+	variables: BTreeMap<String, Bounds>,
+	features: BTreeMap<String, Vec<PathBuf>>,
+}
+
+// "name": rest
+fn quoted(line: &str) -> Option<(&str, &str)> {
+	let line = line.strip_prefix('"')?;
+	let end = line.rfind("\":")?;
+	Some((&line[..end], &line[end + 2..]))
+}
+
+impl Coverage {
+	/// Adds the log of one test:
+	///
+	/// ```text
+	/// "n": min-value-hit max-value-hit
+	/// "a":
+	/// feature "tree is a path": hit
+	/// ```
+	pub fn add(&mut self, test: &Path, log: &str) {
+		for line in log.lines() {
+			if let Some(rest) = line.strip_prefix("feature ") {
+				if let Some((name, hits)) = quoted(rest) {
+					let tests = self.features.entry(name.to_string()).or_default();
+					if hits.split_whitespace().any(|h| h == "hit") {
+						tests.push(test.to_path_buf());
+					}
+				}
+			} else if let Some((name, hits)) = quoted(line) {
+				let bounds = self.variables.entry(name.to_string()).or_default();
+				for hit in hits.split_whitespace() {
+					match hit {
+						"min-value-hit" => bounds.min.push(test.to_path_buf()),
+						"max-value-hit" => bounds.max.push(test.to_path_buf()),
+						_ => {}
+					}
+				}
+			}
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.variables.is_empty() && self.features.is_empty()
+	}
+
+	/// Bounds and features no test reached.
+	pub fn misses(&self) -> usize {
+		let bounds: usize = self
+			.variables
+			.values()
+			.map(|b| b.min.is_empty() as usize + b.max.is_empty() as usize)
+			.sum();
+		bounds + self.features.values().filter(|t| t.is_empty()).count()
+	}
+
+	pub fn print(&self) {
+		println!("{}", "Coverage ..".green());
+		let width = self
+			.variables
+			.keys()
+			.map(|name| name.len())
+			.chain(Some("variable".len()))
+			.max()
+			.unwrap();
+		if !self.variables.is_empty() {
+			println!(
+				"  {:<width$}  {:<28}  max",
+				"variable",
+				"min",
+				width = width
+			);
+			for (name, bounds) in &self.variables {
+				// padded before coloring, escape codes have no width
+				let min = format!("{:<28}", hits(&bounds.min));
+				println!(
+					"  {:<width$}  {}  {}",
+					name,
+					colored(&min, bounds.min.is_empty()),
+					colored(&hits(&bounds.max), bounds.max.is_empty()),
+					width = width
+				);
+			}
+		}
+		for (name, tests) in &self.features {
+			println!(
+				"  feature \"{}\": {}",
+				name,
+				colored(&hits(tests), tests.is_empty())
+			);
+		}
+	}
+}
+
+fn colored(text: &str, missed: bool) -> ColoredString {
+	if missed {
+		text.red()
+	} else {
+		text.normal()
+	}
+}
+
+// never | 1.in, 2.in | 1.in, 2.in, 3.in +4
+fn hits(tests: &[PathBuf]) -> String {
+	if tests.is_empty() {
+		return "never".to_string();
+	}
+	let named: Vec<String> = tests
+		.iter()
+		.take(MAX_NAMED)
+		.map(|t| t.display().to_string())
+		.collect();
+	let mut s = named.join(", ");
+	if tests.len() > MAX_NAMED {
+		s.push_str(&format!(" +{}", tests.len() - MAX_NAMED));
+	}
+	s
+}
@@ -1,21 +1,248 @@
+use crate::weight::{self, Weight, Weights};
 use num_traits::{Num, Zero};
 use rand::distributions::Uniform;
-use rand::Rng;
 use rand::seq::SliceRandom;
-
+use rand::Rng;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Write;
 
 pub struct Range<X> {
 	pub low: X,
 	pub high: X,
 }
 
-pub fn generate_tree<X>(n: usize, weight_range: Option<Range<X>>)
+// vertices are 1..=n, weights[i] belongs to edges[i]
+pub struct Tree<W> {
+	pub n: usize,
+	pub edges: Vec<(usize, usize)>,
+	pub weights: Option<Vec<W>>,
+}
+
+pub struct Graph<W> {
+	pub n: usize,
+	pub directed: bool,
+	pub edges: Vec<(usize, usize)>,
+	pub weights: Option<Vec<W>>,
+}
+
+// counter-clockwise
+pub struct Polygon<X> {
+	pub points: Vec<(X, X)>,
+}
+
+// serialize in the usual input format
+pub trait TestWriter {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()>;
+
+	fn write_stdout(&self) -> std::io::Result<()> {
+		let stdout = std::io::stdout();
+		let mut out = std::io::BufWriter::new(stdout.lock());
+		self.write_to(&mut out)?;
+		out.flush()
+	}
+}
+
+/// Output size limit of a `generate` command.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+	pub max_bytes: Option<usize>,
+	// shrink the parameters instead of failing
+	pub shrink: bool,
+}
+
+/// How far the parameters are scaled down to fit a `Budget`.
+pub struct Shrink {
+	scale: f64,
+	digits: u32,
+	// some count can still go down
+	room: Cell<bool>,
+	// the output has weights whose digits can go down
+	weighted: Cell<bool>,
+}
+
+impl Shrink {
+	fn new() -> Shrink {
+		Shrink {
+			scale: 1.0,
+			digits: 0,
+			room: Cell::new(false),
+			weighted: Cell::new(false),
+		}
+	}
+
+	/// `n` scaled down, never below `min`.
+	pub fn count(&self, n: usize, min: usize) -> usize {
+		self.scaled(n, min, self.scale)
+	}
+
+	/// One side of a 2D output (matrix), so that the area scales like `count`.
+	pub fn side(&self, n: usize, min: usize) -> usize {
+		self.scaled(n, min, self.scale.sqrt())
+	}
+
+	fn scaled(&self, n: usize, min: usize, scale: f64) -> usize {
+		if n <= min {
+			return n;
+		}
+		let m = (n as f64 * scale) as usize;
+		if m > min {
+			self.room.set(true);
+			m
+		} else {
+			min
+		}
+	}
+
+	/// `range` with the last `digits` decimal digits dropped from `high`, kept within `range`:
+	/// `[1, 10^9]` becomes `[1, 10^8]`, `low` never changes.
+	pub fn range<X: Weight>(&self, range: &Range<X>) -> Range<X> {
+		self.weighted.set(true);
+		let high = range.high.drop_digits(self.digits);
+		Range {
+			low: range.low,
+			high: if high < range.low {
+				range.low
+			} else if high > range.high {
+				range.high
+			} else {
+				high
+			},
+		}
+	}
+
+	pub fn weights<X: Weight>(&self, weights: &Weights<X>) -> Weights<X> {
+		Weights {
+			range: self.range(&weights.range),
+			dist: weights.dist,
+		}
+	}
+}
+
+/// Serialize `generate(&shrink)` and write it to stdout if it fits `budget`.
+///
+/// With `budget.shrink`, counts are scaled down by the size ratio until they hit their minimum,
+/// then the weights lose one digit at a time. It fails once a step no longer makes the output smaller.
+pub fn write_within<T, F>(budget: &Budget, mut generate: F) -> Result<(), Box<dyn Error>>
 where
-	X: Num + rand::distributions::uniform::SampleUniform + std::fmt::Display,
+	T: TestWriter,
+	F: FnMut(&Shrink) -> Result<T, Box<dyn Error>>,
 {
+	let mut shrink = Shrink::new();
+	let mut last = usize::MAX;
+	loop {
+		shrink.room.set(false);
+		shrink.weighted.set(false);
+		let mut buf = Vec::new();
+		let test = match generate(&shrink) {
+			// e.g. too few distinct weights left in the range
+			Err(err) if last != usize::MAX => {
+				return Err(format!(
+					"cannot shrink the output below {} bytes (--max-bytes {}): {}",
+					last,
+					budget.max_bytes.unwrap_or(0),
+					err
+				)
+				.into());
+			}
+			test => test?,
+		};
+		test.write_to(&mut buf)?;
+
+		let max = match budget.max_bytes {
+			Some(max) if buf.len() > max => max,
+			_ => {
+				let stdout = std::io::stdout();
+				let mut out = stdout.lock();
+				out.write_all(&buf)?;
+				return Ok(out.flush()?);
+			}
+		};
+		if !budget.shrink {
+			return Err(format!(
+				"output is {} bytes, more than --max-bytes {} (use --shrink to fit)",
+				buf.len(),
+				max
+			)
+			.into());
+		}
+		if buf.len() < last && shrink.room.get() {
+			// a little under the ratio, the size is not exactly linear in the counts
+			shrink.scale *= max as f64 / buf.len() as f64 * 0.99;
+		} else if buf.len() < last && shrink.weighted.get() {
+			shrink.digits += 1;
+		} else {
+			return Err(format!(
+				"cannot shrink the output below {} bytes (--max-bytes {})",
+				buf.len(),
+				max
+			)
+			.into());
+		}
+		last = buf.len();
+	}
+}
+
+fn write_edges<O: Write, W: Display>(
+	out: &mut O,
+	edges: &[(usize, usize)],
+	weights: &Option<Vec<W>>,
+) -> std::io::Result<()> {
+	match weights {
+		Some(weights) => {
+			for (e, w) in edges.iter().zip(weights) {
+				writeln!(out, "{} {} {}", e.0, e.1, w)?;
+			}
+		}
+		None => {
+			for e in edges {
+				writeln!(out, "{} {}", e.0, e.1)?;
+			}
+		}
+	}
+	Ok(())
+}
+
+// n
+// u v (w)
+impl<W: Display> TestWriter for Tree<W> {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		writeln!(out, "{}", self.n)?;
+		write_edges(out, &self.edges, &self.weights)
+	}
+}
+
+// n m
+// u v (w)
+impl<W: Display> TestWriter for Graph<W> {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		writeln!(out, "{} {}", self.n, self.edges.len())?;
+		write_edges(out, &self.edges, &self.weights)
+	}
+}
+
+// n
+// x y
+impl<X: Display> TestWriter for Polygon<X> {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		writeln!(out, "{}", self.points.len())?;
+		for (x, y) in &self.points {
+			writeln!(out, "{} {}", x, y)?;
+		}
+		Ok(())
+	}
+}
+
+// prufer sequence -> uniform labeled tree on 1..=n
+pub fn tree_edges<R: Rng>(rng: &mut R, n: usize) -> Vec<(usize, usize)> {
+	if n < 2 {
+		return Vec::new();
+	}
+
 	let range = Uniform::new(1, n + 1);
 	let binary = Uniform::new(0, 2);
-	let mut rng = rand::thread_rng();
 
 	let prufer: Vec<usize> = (0..n - 2).map(|_| rng.sample(range)).collect();
 	let mut degree = vec![1; n + 1];
@@ -48,33 +275,340 @@ where
 		}
 	}
 
-	(&mut edges[..]).shuffle(&mut rng);
+	(&mut edges[..]).shuffle(rng);
 	for e in &mut edges {
 		if rng.sample(&binary) == 0 {
 			std::mem::swap(&mut e.0, &mut e.1);
 		}
 	}
 
-	println!("{}", n);
-	match weight_range {
-		Some(range) => {
-			let dist = Uniform::new(range.low, range.high);
-			for e in edges {
-				let w = rng.sample(&dist);
-				println!("{} {} {}", e.0, e.1, w);
+	edges
+}
+
+// no constraint -> uniform prufer tree
+#[derive(Debug, Clone, Default)]
+pub struct TreeShape {
+	pub diameter: Option<usize>,
+	// false: `diameter` is a lower bound
+	pub exact_diameter: bool,
+	pub max_degree: Option<usize>,
+	pub min_leaves: Option<usize>,
+}
+
+impl TreeShape {
+	pub fn is_uniform(&self) -> bool {
+		self.diameter.is_none() && self.max_degree.is_none() && self.min_leaves.is_none()
+	}
+}
+
+// pick a random valid node from `pool`, dropping invalid ones on the way
+fn pick<R: Rng, F: Fn(usize) -> bool>(
+	rng: &mut R,
+	pool: &mut Vec<usize>,
+	valid: F,
+) -> Option<usize> {
+	while !pool.is_empty() {
+		let i = rng.gen_range(0..pool.len());
+		if valid(pool[i]) {
+			return Some(pool[i]);
+		}
+		pool.swap_remove(i);
+	}
+	None
+}
+
+// spine 0..=D is the diameter path, every other vertex hangs under a parent with spare
+// degree. A vertex at distance h from spine vertex i keeps the diameter iff h <= min(i, D - i).
+fn try_shaped_tree<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	shape: &TreeShape,
+) -> Option<Vec<(usize, usize)>> {
+	let d = shape.diameter.unwrap_or(0);
+	let max_degree = shape.max_degree.unwrap_or(usize::MAX);
+	let min_leaves = shape.min_leaves.unwrap_or(0);
+
+	let mut degree = vec![0; n];
+	// how many more levels may hang below each vertex
+	let mut room = vec![0; n];
+	let mut edges: Vec<(usize, usize)> = Vec::with_capacity(n - 1);
+
+	for i in 0..d + 1 {
+		room[i] = if shape.exact_diameter && shape.diameter.is_some() {
+			std::cmp::min(i, d - i)
+		} else {
+			usize::MAX
+		};
+		if i > 0 {
+			edges.push((i - 1, i));
+			degree[i - 1] += 1;
+			degree[i] += 1;
+		}
+	}
+	if degree.iter().any(|&deg| deg > max_degree) {
+		return None;
+	}
+
+	let mut leaves = degree.iter().filter(|&&deg| deg == 1).count() + (n == 1) as usize;
+	// every vertex that may take a child, and those among them that are not leaves
+	let mut any: Vec<usize> = (0..d + 1).filter(|&v| room[v] > 0).collect();
+	let mut inner: Vec<usize> = any.iter().cloned().filter(|&v| degree[v] != 1).collect();
+
+	for v in d + 1..n {
+		let left = n - v;
+		let need_leaf = min_leaves > leaves && min_leaves - leaves >= left;
+		let u = {
+			let degree = &degree;
+			let valid = |u: usize| degree[u] < max_degree;
+			if need_leaf {
+				// hanging under a leaf keeps the leaf count, so avoid leaves
+				pick(rng, &mut inner, |u| valid(u) && degree[u] != 1)?
+			} else {
+				pick(rng, &mut any, valid)?
 			}
+		};
+
+		match degree[u] {
+			0 => leaves += 2,
+			1 => {}
+			_ => leaves += 1,
 		}
-		None => {
-			for e in edges {
-				println!("{} {}", e.0, e.1);
+		degree[u] += 1;
+		degree[v] = 1;
+		room[v] = room[u] - 1;
+		edges.push((u, v));
+
+		if degree[u] == 2 {
+			inner.push(u);
+		}
+		if room[v] > 0 {
+			any.push(v);
+		}
+	}
+
+	if leaves < min_leaves {
+		return None;
+	}
+	Some(edges)
+}
+
+/// Random tree on `1..=n` with the given diameter, maximum degree and minimum number of leaves.
+pub fn shaped_tree_edges<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	shape: &TreeShape,
+) -> Result<Vec<(usize, usize)>, Box<dyn Error>> {
+	if shape.is_uniform() {
+		return Ok(tree_edges(rng, n));
+	}
+	if n == 0 {
+		return Ok(Vec::new());
+	}
+	if let Some(d) = shape.diameter {
+		if d + 1 > n {
+			return Err(format!("diameter {} needs at least {} vertices", d, d + 1).into());
+		}
+		if shape.exact_diameter && d == 0 && n > 1 {
+			return Err("only a single vertex has diameter 0".into());
+		}
+	}
+
+	for _ in 0..10 {
+		if let Some(edges) = try_shaped_tree(rng, n, shape) {
+			let label = permutation(rng, n);
+			let mut edges: Vec<(usize, usize)> = edges
+				.into_iter()
+				.map(|(u, v)| {
+					if rng.gen::<bool>() {
+						(label[u], label[v])
+					} else {
+						(label[v], label[u])
+					}
+				})
+				.collect();
+			edges.shuffle(rng);
+			return Ok(edges);
+		}
+	}
+	Err(format!("cannot build a tree with {} vertices and {:?}", n, shape).into())
+}
+
+// uniform sample from [low, high]
+pub fn random_array<X, R>(rng: &mut R, n: usize, value_range: &Range<X>) -> Vec<X>
+where
+	X: rand::distributions::uniform::SampleUniform + Copy,
+	R: Rng,
+{
+	let dist = Uniform::new_inclusive(value_range.low, value_range.high);
+	(0..n).map(|_| rng.sample(&dist)).collect()
+}
+
+// random permutation of 1..=n
+pub fn permutation<R: Rng>(rng: &mut R, n: usize) -> Vec<usize> {
+	let mut p: Vec<usize> = (1..n + 1).collect();
+	p.shuffle(rng);
+	p
+}
+
+pub fn random_tree<X, R>(
+	rng: &mut R,
+	n: usize,
+	shape: &TreeShape,
+	directed: bool,
+	weights: Option<&Weights<X>>,
+) -> Result<Tree<X>, Box<dyn Error>>
+where
+	X: Weight,
+	R: Rng,
+{
+	let edges = shaped_tree_edges(rng, n, shape)?;
+	// an undirected edge with a negative weight is a negative cycle by itself
+	let weights = match weights {
+		Some(w) => Some(weight::sample_edge_weights(rng, n, &edges, directed, w)?),
+		None => None,
+	};
+	Ok(Tree { n, edges, weights })
+}
+
+// simple graph (no loops, no multi-edges) on 1..=n
+// connected: contains a random spanning tree (weakly connected if directed)
+pub fn random_graph<X, R>(
+	rng: &mut R,
+	n: usize,
+	m: usize,
+	connected: bool,
+	directed: bool,
+	weights: Option<&Weights<X>>,
+) -> Result<Graph<X>, Box<dyn Error>>
+where
+	X: Weight,
+	R: Rng,
+{
+	let max_m = if directed {
+		n * n.saturating_sub(1)
+	} else {
+		n * n.saturating_sub(1) / 2
+	};
+	if m > max_m {
+		return Err(format!("too many edges: {} > {}", m, max_m).into());
+	}
+	if connected && m + 1 < n {
+		return Err(format!("connected graph needs at least {} edges", n - 1).into());
+	}
+
+	let key = |u: usize, v: usize| if directed || u < v { (u, v) } else { (v, u) };
+	let mut used: HashSet<(usize, usize)> = HashSet::with_capacity(m);
+	let mut edges: Vec<(usize, usize)> = Vec::with_capacity(m);
+
+	if connected {
+		for (u, v) in tree_edges(rng, n) {
+			used.insert(key(u, v));
+			edges.push((u, v));
+		}
+	}
+
+	if (m - edges.len()) * 2 > max_m {
+		// dense: pick from the remaining pairs
+		let mut rest: Vec<(usize, usize)> = Vec::with_capacity(max_m - edges.len());
+		for u in 1..n + 1 {
+			for v in 1..n + 1 {
+				if u != v && key(u, v) == (u, v) && !used.contains(&(u, v)) {
+					rest.push((u, v));
+				}
+			}
+		}
+		rest.shuffle(rng);
+		rest.truncate(m - edges.len());
+		edges.extend(rest);
+	} else if edges.len() < m {
+		let vertex = Uniform::new(1, n + 1);
+		while edges.len() < m {
+			let u = rng.sample(vertex);
+			let v = rng.sample(vertex);
+			if u != v && used.insert(key(u, v)) {
+				edges.push((u, v));
 			}
 		}
 	}
+
+	edges.shuffle(rng);
+	if !directed {
+		for e in &mut edges {
+			if rng.gen::<bool>() {
+				std::mem::swap(&mut e.0, &mut e.1);
+			}
+		}
+	}
+
+	let weights = match weights {
+		Some(w) => Some(weight::sample_edge_weights(rng, n, &edges, directed, w)?),
+		None => None,
+	};
+	Ok(Graph {
+		n,
+		directed,
+		edges,
+		weights,
+	})
+}
+
+pub fn generate_tree<X: Weight>(
+	n: usize,
+	shape: &TreeShape,
+	directed: bool,
+	weights: Option<Weights<X>>,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let mut rng = rand::thread_rng();
+	// the spine and the leaves need this many vertices
+	let min_n = std::cmp::max(
+		shape.diameter.map_or(1, |d| d + 1),
+		shape.min_leaves.map_or(1, |l| l + 1),
+	);
+	write_within(budget, |s| {
+		let weights = weights.as_ref().map(|w| s.weights(w));
+		random_tree(
+			&mut rng,
+			s.count(n, min_n),
+			shape,
+			directed,
+			weights.as_ref(),
+		)
+	})
+}
+
+pub fn generate_graph<X: Weight>(
+	n: usize,
+	m: usize,
+	connected: bool,
+	directed: bool,
+	weights: Option<Weights<X>>,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let mut rng = rand::thread_rng();
+	write_within(budget, |s| {
+		let weights = weights.as_ref().map(|w| s.weights(w));
+		// m goes down first, a connected graph then keeps n <= m + 1
+		let shrunk_m = s.count(m, 0);
+		let shrunk_n = if connected && shrunk_m < m {
+			std::cmp::min(n, shrunk_m + 1)
+		} else {
+			n
+		};
+		random_graph(
+			&mut rng,
+			shrunk_n,
+			shrunk_m,
+			connected,
+			directed,
+			weights.as_ref(),
+		)
+	})
 }
 
 // valtr algo
 // http://cglab.ca/~sander/misc/ConvexGeneration/convex.html
-pub fn generate_convex<X>(n: usize, coord_range: Range<X>)
+pub fn convex_polygon<X, R>(rng: &mut R, n: usize, coord_range: &Range<X>) -> Vec<(X, X)>
 where
 	X: Num
 		+ rand::distributions::uniform::SampleUniform
@@ -83,16 +617,15 @@ where
 		+ num_traits::Signed
 		+ std::ops::AddAssign
 		+ num_traits::AsPrimitive<i8>,
+	R: Rng,
 {
 	assert!(n >= 3);
 
 	let coord_dist = Uniform::new(coord_range.low, coord_range.high);
-	let mut rng = rand::thread_rng();
 
 	// random points in square -> O(n^n) trials in average
-	let generate_chains = &|| -> (X, Vec<X>) {
+	let generate_chains = |rng: &mut R| -> (X, Vec<X>) {
 		let binary = Uniform::new(0, 2);
-		let mut rng = rand::thread_rng();
 		// return n vectors that sums up to 0
 		let mut a: Vec<X> = (0..n).map(|_| rng.sample(&coord_dist)).collect();
 		a.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
@@ -113,10 +646,10 @@ where
 		(a[1], chains)
 	};
 
-	let (minx, mut xs) = generate_chains();
-	let (miny, ys) = generate_chains();
+	let (minx, mut xs) = generate_chains(rng);
+	let (miny, ys) = generate_chains(rng);
 
-	(&mut xs[..]).shuffle(&mut rng);
+	(&mut xs[..]).shuffle(rng);
 	// rng.shuffle(&mut xs);
 	// rng.shuffle(&mut ys);
 	let mut vec: Vec<(X, X)> = (0..n).map(|i| (xs[i], ys[i])).collect();
@@ -163,13 +696,11 @@ where
 	}
 
 	// shift my -> miny
-	// let ret:Vec<(,)> = Vec::with_capacity(n);
+	let mut ret: Vec<(X, X)> = Vec::with_capacity(n);
 	let mut x = minx;
 	y = miny - my;
-	println!("{}", n);
 	for i in 0..n {
-		// ret.push((x,y));
-		println!("{} {}", x, y);
+		ret.push((x, y));
 		// println!("+= {} {}", vec[i].0, vec[i].1);
 		x += vec[i].0;
 		y += vec[i].1;
@@ -179,4 +710,160 @@ where
 	// assert!((y-(miny-my)).is_zero(), "sum of vector y = {} {} ", y,miny-my);
 	// 3.0644979766624543
 	// 3.0644979766624516
+	ret
+}
+
+pub fn random_polygon<X, R>(rng: &mut R, n: usize, coord_range: &Range<X>) -> Polygon<X>
+where
+	X: Num
+		+ rand::distributions::uniform::SampleUniform
+		+ std::fmt::Display
+		+ std::cmp::PartialOrd
+		+ num_traits::Signed
+		+ std::ops::AddAssign
+		+ num_traits::AsPrimitive<i8>,
+	R: Rng,
+{
+	Polygon {
+		points: convex_polygon(rng, n, coord_range),
+	}
+}
+
+pub fn generate_convex<X>(
+	n: usize,
+	coord_range: Range<X>,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>>
+where
+	X: Num
+		+ rand::distributions::uniform::SampleUniform
+		+ std::fmt::Display
+		+ std::cmp::PartialOrd
+		+ num_traits::Signed
+		+ std::ops::AddAssign
+		+ num_traits::AsPrimitive<i8>,
+{
+	if n < 3 {
+		return Err("convex polygon needs n >= 3".into());
+	}
+	// the coordinates are drawn from [low, high)
+	if coord_range.low >= coord_range.high {
+		return Err(format!(
+			"convex polygon needs low < high: [{}, {}]",
+			coord_range.low, coord_range.high
+		)
+		.into());
+	}
+	let mut rng = rand::thread_rng();
+	write_within(budget, |s| {
+		Ok(random_polygon(&mut rng, s.count(n, 3), &coord_range))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn adjacency(n: usize, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+		assert_eq!(edges.len(), n - 1);
+		let mut adj = vec![Vec::new(); n + 1];
+		for &(u, v) in edges {
+			adj[u].push(v);
+			adj[v].push(u);
+		}
+		adj
+	}
+
+	// the farthest vertex from `s` and its distance, panics unless connected
+	fn farthest(adj: &[Vec<usize>], s: usize) -> (usize, usize) {
+		let mut dist = vec![usize::MAX; adj.len()];
+		dist[s] = 0;
+		let mut queue = std::collections::VecDeque::from([s]);
+		let mut last = s;
+		while let Some(u) = queue.pop_front() {
+			last = u;
+			for &v in &adj[u] {
+				if dist[v] == usize::MAX {
+					dist[v] = dist[u] + 1;
+					queue.push_back(v);
+				}
+			}
+		}
+		assert!(dist[1..].iter().all(|&d| d != usize::MAX));
+		(last, dist[last])
+	}
+
+	fn diameter(adj: &[Vec<usize>]) -> usize {
+		farthest(adj, farthest(adj, 1).0).1
+	}
+
+	fn shape(diameter: Option<usize>, exact: bool, max_degree: Option<usize>) -> TreeShape {
+		TreeShape {
+			diameter,
+			exact_diameter: exact,
+			max_degree,
+			min_leaves: None,
+		}
+	}
+
+	#[test]
+	fn exact_diameter() {
+		let mut rng = rand::thread_rng();
+		for (n, d) in [(2, 1), (10, 2), (10, 9), (100, 7), (100, 50)] {
+			let edges = shaped_tree_edges(&mut rng, n, &shape(Some(d), true, None)).unwrap();
+			assert_eq!(diameter(&adjacency(n, &edges)), d, "n = {}", n);
+		}
+	}
+
+	#[test]
+	fn min_diameter() {
+		let mut rng = rand::thread_rng();
+		for (n, d) in [(10, 3), (100, 20)] {
+			let edges = shaped_tree_edges(&mut rng, n, &shape(Some(d), false, None)).unwrap();
+			assert!(diameter(&adjacency(n, &edges)) >= d);
+		}
+	}
+
+	#[test]
+	fn max_degree() {
+		let mut rng = rand::thread_rng();
+		for (n, k) in [(10, 2), (100, 3), (1000, 4)] {
+			let edges = shaped_tree_edges(&mut rng, n, &shape(None, false, Some(k))).unwrap();
+			let adj = adjacency(n, &edges);
+			assert!(adj.iter().all(|a| a.len() <= k));
+			// connected
+			farthest(&adj, 1);
+		}
+		// a path is the only tree with maximum degree 2
+		let edges = shaped_tree_edges(&mut rng, 50, &shape(None, false, Some(2))).unwrap();
+		assert_eq!(diameter(&adjacency(50, &edges)), 49);
+	}
+
+	#[test]
+	fn diameter_and_degree() {
+		let mut rng = rand::thread_rng();
+		let edges = shaped_tree_edges(&mut rng, 200, &shape(Some(20), true, Some(3))).unwrap();
+		let adj = adjacency(200, &edges);
+		assert_eq!(diameter(&adj), 20);
+		assert!(adj.iter().all(|a| a.len() <= 3));
+	}
+
+	#[test]
+	fn impossible_shapes() {
+		let mut rng = rand::thread_rng();
+		assert!(shaped_tree_edges(&mut rng, 5, &shape(Some(5), true, None)).is_err());
+		assert!(shaped_tree_edges(&mut rng, 5, &shape(Some(0), true, None)).is_err());
+		// a binary tree of depth 2 has at most 7 vertices
+		assert!(shaped_tree_edges(&mut rng, 20, &shape(Some(2), true, Some(3))).is_err());
+	}
+
+	#[test]
+	fn empty_graph() {
+		let mut rng = rand::thread_rng();
+		for connected in [false, true] {
+			let g = random_graph::<i8, _>(&mut rng, 0, 0, connected, false, None).unwrap();
+			assert!(g.edges.is_empty());
+		}
+		assert!(random_graph::<i8, _>(&mut rng, 0, 1, false, false, None).is_err());
+	}
 }
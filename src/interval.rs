@@ -0,0 +1,195 @@
+use crate::generate::{write_within, Budget, Range, TestWriter};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::error::Error;
+use std::io::Write;
+
+pub const INTERVAL_MODES: &[&str] = &[
+	"random",
+	"disjoint",
+	"nested",
+	"laminar",
+	"common",
+	"equal-endpoints",
+	"mix",
+];
+
+// closed intervals, l <= r
+pub struct Intervals {
+	pub items: Vec<(i64, i64)>,
+}
+
+// n
+// l r
+impl TestWriter for Intervals {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		writeln!(out, "{}", self.items.len())?;
+		for (l, r) in &self.items {
+			writeln!(out, "{} {}", l, r)?;
+		}
+		Ok(())
+	}
+}
+
+fn ordered(a: i64, b: i64) -> (i64, i64) {
+	if a <= b {
+		(a, b)
+	} else {
+		(b, a)
+	}
+}
+
+// k distinct sorted coordinates
+fn distinct_points<R: Rng>(
+	rng: &mut R,
+	k: usize,
+	range: &Range<i64>,
+) -> Result<Vec<i64>, Box<dyn Error>> {
+	let size = (range.high as i128 - range.low as i128 + 1).max(0) as u128;
+	if size < k as u128 {
+		return Err(format!(
+			"[{}, {}] has fewer than {} distinct points",
+			range.low, range.high, k
+		)
+		.into());
+	}
+	let mut v: Vec<i64> = if size > usize::MAX as u128 {
+		let mut seen = std::collections::HashSet::with_capacity(k);
+		while seen.len() < k {
+			seen.insert(rng.gen_range(range.low..=range.high));
+		}
+		seen.into_iter().collect()
+	} else {
+		rand::seq::index::sample(rng, size as usize, k)
+			.into_iter()
+			.map(|i| (range.low as i128 + i as i128) as i64)
+			.collect()
+	};
+	v.sort_unstable();
+	Ok(v)
+}
+
+pub fn random_intervals<R: Rng>(rng: &mut R, n: usize, range: &Range<i64>) -> Vec<(i64, i64)> {
+	(0..n)
+		.map(|_| {
+			ordered(
+				rng.gen_range(range.low..=range.high),
+				rng.gen_range(range.low..=range.high),
+			)
+		})
+		.collect()
+}
+
+/// No two intervals share a point.
+pub fn disjoint_intervals<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	range: &Range<i64>,
+) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
+	let p = distinct_points(rng, 2 * n, range)?;
+	Ok(p.chunks(2).map(|c| (c[0], c[1])).collect())
+}
+
+/// A chain: each interval contains the next one.
+pub fn nested_intervals<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	range: &Range<i64>,
+) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
+	let p = distinct_points(rng, 2 * n, range)?;
+	Ok((0..n).map(|i| (p[i], p[2 * n - 1 - i])).collect())
+}
+
+/// Any two intervals are nested or disjoint, matched brackets of a random balanced sequence.
+pub fn laminar_intervals<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	range: &Range<i64>,
+) -> Result<Vec<(i64, i64)>, Box<dyn Error>> {
+	let p = distinct_points(rng, 2 * n, range)?;
+	let mut items = Vec::with_capacity(n);
+	let mut stack = Vec::with_capacity(n);
+	let mut opened = 0;
+	for &x in &p {
+		if opened < n && (stack.is_empty() || rng.gen::<bool>()) {
+			stack.push(x);
+			opened += 1;
+		} else {
+			let l = stack.pop().unwrap();
+			items.push((l, x));
+		}
+	}
+	Ok(items)
+}
+
+/// Every interval contains one common point.
+pub fn common_point_intervals<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	range: &Range<i64>,
+) -> Vec<(i64, i64)> {
+	let x = rng.gen_range(range.low..=range.high);
+	(0..n)
+		.map(|_| (rng.gen_range(range.low..=x), rng.gen_range(x..=range.high)))
+		.collect()
+}
+
+/// Endpoints come from about `sqrt(n)` coordinates, so equal endpoints (and `l == r`) are common.
+pub fn equal_endpoint_intervals<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	range: &Range<i64>,
+) -> Vec<(i64, i64)> {
+	let k = std::cmp::max(2, (n as f64).sqrt() as usize);
+	let pool: Vec<i64> = (0..k)
+		.map(|_| rng.gen_range(range.low..=range.high))
+		.collect();
+	(0..n)
+		.map(|_| ordered(*pool.choose(rng).unwrap(), *pool.choose(rng).unwrap()))
+		.collect()
+}
+
+pub fn intervals<R: Rng>(
+	rng: &mut R,
+	mode: &str,
+	n: usize,
+	range: &Range<i64>,
+) -> Result<Intervals, Box<dyn Error>> {
+	if range.low > range.high {
+		return Err(format!("empty range: [{}, {}]", range.low, range.high).into());
+	}
+	let mut items = match mode {
+		"random" => random_intervals(rng, n, range),
+		"disjoint" => disjoint_intervals(rng, n, range)?,
+		"nested" => nested_intervals(rng, n, range)?,
+		"laminar" => laminar_intervals(rng, n, range)?,
+		"common" => common_point_intervals(rng, n, range),
+		"equal-endpoints" => equal_endpoint_intervals(rng, n, range),
+		"mix" => {
+			// split n among the other modes, each family on its own
+			let modes = &INTERVAL_MODES[..INTERVAL_MODES.len() - 1];
+			let mut cuts: Vec<usize> = (0..modes.len() - 1).map(|_| rng.gen_range(0..=n)).collect();
+			cuts.push(0);
+			cuts.push(n);
+			cuts.sort_unstable();
+			let mut items = Vec::with_capacity(n);
+			for (mode, w) in modes.iter().zip(cuts.windows(2)) {
+				items.extend(intervals(rng, mode, w[1] - w[0], range)?.items);
+			}
+			items
+		}
+		_ => return Err(format!("unknown mode: {}", mode).into()),
+	};
+	items.shuffle(rng);
+	Ok(Intervals { items })
+}
+
+pub fn generate_intervals(
+	mode: &str,
+	n: usize,
+	range: &Range<i64>,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let mut rng = rand::thread_rng();
+	write_within(budget, |s| intervals(&mut rng, mode, s.count(n, 1), range))
+}
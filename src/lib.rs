@@ -0,0 +1,20 @@
+//! Library side of `psutil`.
+//!
+//! Generators return plain data (`generate::Tree`, `generate::Graph`, `generate::Polygon`)
+//! which can be serialized with `generate::TestWriter`, and `runner` can evaluate
+//! or validate single cases from a Rust test harness.
+
+pub mod audit;
+pub mod compile;
+pub mod coverage;
+pub mod generate;
+pub mod interval;
+pub mod location;
+pub mod matrix;
+pub mod number;
+pub mod runner;
+pub mod samples;
+pub mod sandbox;
+pub mod sanitize;
+pub mod script;
+pub mod weight;
@@ -0,0 +1,101 @@
+// line and column of a byte offset, with a short escaped snippet around it
+
+// bytes of context on each side of a snippet
+const CONTEXT: usize = 24;
+
+pub struct Locator<'a> {
+	data: &'a [u8],
+	// offsets of every \n
+	newlines: Vec<usize>,
+}
+
+impl<'a> Locator<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		let newlines = data
+			.iter()
+			.enumerate()
+			.filter(|&(_, &b)| b == b'\n')
+			.map(|(i, _)| i)
+			.collect();
+		Locator { data, newlines }
+	}
+
+	/// 1-based line and column of `offset`, the column counts bytes.
+	pub fn position(&self, offset: usize) -> (usize, usize) {
+		let line = self.newlines.partition_point(|&i| i < offset);
+		let start = if line == 0 {
+			0
+		} else {
+			self.newlines[line - 1] + 1
+		};
+		(line + 1, offset - start + 1)
+	}
+
+	/// Line `n` (1-based) without its \n, empty past the end.
+	pub fn line(&self, n: usize) -> &'a [u8] {
+		if n == 0 || n > self.newlines.len() + 1 {
+			return &[];
+		}
+		let start = if n == 1 { 0 } else { self.newlines[n - 2] + 1 };
+		let end = self.newlines.get(n - 1).cloned().unwrap_or(self.data.len());
+		&self.data[start..end]
+	}
+
+	/// The line around `offset`, cut to `CONTEXT` bytes on each side and escaped.
+	pub fn snippet(&self, offset: usize) -> String {
+		let (line, column) = self.position(offset);
+		cut(self.line(line), column - 1)
+	}
+
+	/// Line `n` (1-based) from its start, cut and escaped like `snippet`.
+	pub fn line_snippet(&self, n: usize) -> String {
+		let text = self.line(n);
+		cut(text, std::cmp::min(CONTEXT, text.len()))
+	}
+}
+
+fn cut(text: &[u8], at: usize) -> String {
+	let at = std::cmp::min(at, text.len());
+	let mut from = at.saturating_sub(CONTEXT);
+	let mut to = std::cmp::min(text.len(), at + CONTEXT);
+	// do not cut a UTF-8 sequence
+	let continuation = |i: usize| i < text.len() && text[i] & 0xC0 == 0x80;
+	while from < at && continuation(from) {
+		from += 1;
+	}
+	while to > at && continuation(to) {
+		to -= 1;
+	}
+	format!(
+		"{}\"{}\"{}",
+		if from > 0 { ".." } else { "" },
+		escape(&text[from..to]),
+		if to < text.len() { ".." } else { "" }
+	)
+}
+
+/// Printable ASCII and letters as they are, everything else escaped: `\r`, `\t`, `\u{a0}`, `\xff`.
+pub fn escape(bytes: &[u8]) -> String {
+	let mut s = String::new();
+	for chunk in bytes.utf8_chunks() {
+		for c in chunk.valid().chars() {
+			let plain = c == ' ' || c.is_ascii_graphic() && c != '"' && c != '\\';
+			// letters of other scripts are kept readable
+			let letter = !c.is_ascii() && c.is_alphabetic() && !is_wide_ascii(c);
+			if plain || letter {
+				s.push(c);
+			} else {
+				s.extend(c.escape_default());
+			}
+		}
+		for b in chunk.invalid() {
+			s.push_str(&format!("\\x{:02x}", b));
+		}
+	}
+	s
+}
+
+// full-width forms of ASCII are letters too, but they are what we look for
+fn is_wide_ascii(c: char) -> bool {
+	('\u{FF01}'..='\u{FF5E}').contains(&c)
+}
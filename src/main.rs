@@ -4,21 +4,22 @@ use piston_window::*;
 use std::path::Path;
 
 mod cli;
-mod generate;
-use self::generate::Range;
+use ps_util::generate::{self, Budget, Range, TreeShape};
+use ps_util::weight::{WeightDist, Weights};
 mod codeforces;
 mod judge;
 use self::judge::*;
-mod runner;
-mod sanitize;
+use ps_util::matrix::MatrixOptions;
+use ps_util::{audit, interval, matrix, number, runner, samples, sanitize, script};
 
-mod sandbox;
 mod session;
 
 use std::fs::File;
 use std::io::Write;
 
-use crate::sandbox::Limitation;
+use ps_util::compile::Compiler;
+use ps_util::sandbox::Limitation;
+use ps_util::sanitize::{Mode, Rule};
 
 /**
  * ProblemSet
@@ -53,6 +54,47 @@ fn show() {
     }
 }
 
+fn exit_on_error(result: Result<(), Box<dyn std::error::Error>>) {
+    if let Err(err) = result {
+        eprintln!("[Error] {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn compiler(matches: &clap::ArgMatches) -> Compiler {
+    let mut compiler = Compiler::default();
+    if let Some(cxx) = matches.value_of("cxx") {
+        compiler.cxx = cxx.to_string();
+    }
+    if let Some(flags) = matches.value_of("cxxflags") {
+        compiler.flags = flags.split_whitespace().map(String::from).collect();
+    }
+    compiler
+}
+
+// exits on a value that does not parse
+fn optional_arg<T>(matches: &clap::ArgMatches, name: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    matches.value_of(name).map(|s| {
+        s.parse().unwrap_or_else(|err| {
+            eprintln!("[Error] cannot read {} {:?}: {}", name, s, err);
+            std::process::exit(1);
+        })
+    })
+}
+
+// a .cpp source is compiled first
+fn executable(path: &Path, compiler: &Compiler) -> std::path::PathBuf {
+    compiler.executable(path).unwrap_or_else(|err| {
+        eprintln!("[Error] {}", err);
+        eprintln!("\t=> {}", path.display());
+        std::process::exit(1);
+    })
+}
+
 mod vendor {
     trait Judge {
         fn overview() {}
@@ -69,31 +111,159 @@ fn main() {
     match sub {
         "generate" => {
             let (sub, matches) = matches.subcommand().unwrap();
+            let budget = Budget {
+                max_bytes: optional_arg(matches, "max-bytes"),
+                shrink: matches.is_present("shrink"),
+            };
             match sub {
                 "tree" => {
                     let n = matches.value_of("n").unwrap().parse().unwrap();
-                    if let Some(mut w) = matches.values_of("int-weight") {
+                    let read = |name: &str| optional_arg(matches, name);
+                    let shape = TreeShape {
+                        diameter: read("diameter").or(read("min-diameter")),
+                        exact_diameter: matches.is_present("diameter"),
+                        max_degree: read("max-degree"),
+                        min_leaves: read("min-leaves"),
+                    };
+                    let directed = matches.is_present("directed");
+                    let dist: WeightDist = matches
+                        .value_of("dist")
+                        .unwrap_or("uniform")
+                        .parse()
+                        .unwrap();
+                    let result = if let Some(mut w) = matches.values_of("int-weight") {
                         let low: i64 = w.next().unwrap().parse().unwrap();
                         let high: i64 = w.next().unwrap().parse().unwrap();
-                        generate::generate_tree(n, Some(Range { low, high }));
+                        let range = Range { low, high };
+                        generate::generate_tree(
+                            n,
+                            &shape,
+                            directed,
+                            Some(Weights { range, dist }),
+                            &budget,
+                        )
                     } else if let Some(mut w) = matches.values_of("float-weight") {
                         let low: f64 = w.next().unwrap().parse().unwrap();
                         let high: f64 = w.next().unwrap().parse().unwrap();
-                        generate::generate_tree(n, Some(Range { low, high }));
+                        let range = Range { low, high };
+                        generate::generate_tree(
+                            n,
+                            &shape,
+                            directed,
+                            Some(Weights { range, dist }),
+                            &budget,
+                        )
                     } else {
-                        generate::generate_tree::<i8>(n, None);
-                    }
+                        generate::generate_tree::<i8>(n, &shape, directed, None, &budget)
+                    };
+                    exit_on_error(result);
+                }
+                "graph" => {
+                    let n = matches.value_of("n").unwrap().parse().unwrap();
+                    let m = matches.value_of("m").unwrap().parse().unwrap();
+                    let connected = matches.is_present("connected");
+                    let directed = matches.is_present("directed");
+                    let dist: WeightDist = matches
+                        .value_of("dist")
+                        .unwrap_or("uniform")
+                        .parse()
+                        .unwrap();
+                    let result = if let Some(mut w) = matches.values_of("int-weight") {
+                        let low: i64 = w.next().unwrap().parse().unwrap();
+                        let high: i64 = w.next().unwrap().parse().unwrap();
+                        let range = Range { low, high };
+                        generate::generate_graph(
+                            n,
+                            m,
+                            connected,
+                            directed,
+                            Some(Weights { range, dist }),
+                            &budget,
+                        )
+                    } else if let Some(mut w) = matches.values_of("float-weight") {
+                        let low: f64 = w.next().unwrap().parse().unwrap();
+                        let high: f64 = w.next().unwrap().parse().unwrap();
+                        let range = Range { low, high };
+                        generate::generate_graph(
+                            n,
+                            m,
+                            connected,
+                            directed,
+                            Some(Weights { range, dist }),
+                            &budget,
+                        )
+                    } else {
+                        generate::generate_graph::<i8>(n, m, connected, directed, None, &budget)
+                    };
+                    exit_on_error(result);
                 }
                 "convex" => {
                     let n = matches.value_of("n").unwrap().parse().unwrap();
                     if let Some(mut w) = matches.values_of("int-range") {
                         let low: i64 = w.next().unwrap().parse().unwrap();
                         let high: i64 = w.next().unwrap().parse().unwrap();
-                        generate::generate_convex(n, Range { low, high });
+                        exit_on_error(generate::generate_convex(n, Range { low, high }, &budget));
                     } else if let Some(mut w) = matches.values_of("float-range") {
                         let low: f64 = w.next().unwrap().parse().unwrap();
                         let high: f64 = w.next().unwrap().parse().unwrap();
-                        generate::generate_convex(n, Range { low, high });
+                        exit_on_error(generate::generate_convex(n, Range { low, high }, &budget));
+                    }
+                }
+                "numbers" => {
+                    let mode = matches.value_of("mode").unwrap();
+                    let count = matches.value_of("count").unwrap().parse().unwrap();
+                    let bound = matches.value_of("bound").unwrap().parse().unwrap();
+                    let bases: Vec<u64> = matches
+                        .values_of("bases")
+                        .map(|v| v.map(|b| b.parse().expect("cannot read base")).collect())
+                        .unwrap_or_else(|| vec![2]);
+                    exit_on_error(number::generate_numbers(
+                        mode, count, bound, &bases, &budget,
+                    ));
+                }
+                "matrix" => {
+                    let r = matches.value_of("r").unwrap().parse().unwrap();
+                    let c = matches.value_of("c").unwrap().parse().unwrap();
+                    let mode = matches.value_of("mode").unwrap();
+                    let value_range = matches.values_of("int-range").map(|mut w| {
+                        let low: i64 = w.next().unwrap().parse().unwrap();
+                        let high: i64 = w.next().unwrap().parse().unwrap();
+                        Range { low, high }
+                    });
+                    let opt = MatrixOptions {
+                        value_range,
+                        rank: optional_arg(matches, "rank"),
+                        modulus: optional_arg(matches, "mod"),
+                        edges: optional_arg(matches, "edges"),
+                        connected: matches.is_present("connected"),
+                        directed: matches.is_present("directed"),
+                    };
+                    exit_on_error(matrix::generate_matrix(mode, r, c, &opt, &budget));
+                }
+                "intervals" => {
+                    let n = matches.value_of("n").unwrap().parse().unwrap();
+                    let mode = matches.value_of("mode").unwrap();
+                    let mut w = matches.values_of("int-range").unwrap();
+                    let low: i64 = w.next().unwrap().parse().unwrap();
+                    let high: i64 = w.next().unwrap().parse().unwrap();
+                    exit_on_error(interval::generate_intervals(
+                        mode,
+                        n,
+                        &Range { low, high },
+                        &budget,
+                    ));
+                }
+                "script" => {
+                    let path = matches.value_of("path").unwrap();
+                    let args: Vec<&str> = matches
+                        .values_of("args")
+                        .map(|v| v.collect())
+                        .unwrap_or_default();
+                    let seed: Option<u64> = optional_arg(matches, "seed");
+                    if let Err(err) = script::run_script(path, args, seed, &budget) {
+                        eprintln!("[Error] {}", err);
+                        eprintln!("\t=> {}", path);
+                        std::process::exit(1);
                     }
                 }
                 _ => unreachable!(),
@@ -103,13 +273,142 @@ fn main() {
             let path = matches.value_of("path").unwrap_or(".");
             let exts: Vec<&str> = matches.values_of("ext").unwrap().collect();
             let confirmed = matches.is_present("confirmed");
-            sanitize::sanitize(path, exts, confirmed);
+            let mut rules = match matches.value_of("config") {
+                Some(config) => sanitize::RuleSet::load(config).unwrap_or_else(|err| {
+                    eprintln!("[Error] {}", err);
+                    eprintln!("\t=> {}", config);
+                    std::process::exit(1);
+                }),
+                None => sanitize::RuleSet::default(),
+            };
+            if matches.is_present("utf8") {
+                rules.utf8();
+            }
+            for (name, mode) in [
+                ("fix", Mode::Fix),
+                ("check", Mode::Check),
+                ("skip", Mode::Off),
+            ] {
+                for rule in matches.values_of(name).into_iter().flatten() {
+                    let rule: Rule = rule.parse().unwrap();
+                    if let Err(err) = rules.set(rule, mode) {
+                        eprintln!("[Error] {}", err);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let options = sanitize::Options {
+                rules,
+                confirmed,
+                backup_dir: matches.value_of("backup").map(Path::new),
+                jobs: optional_arg(matches, "jobs").unwrap_or(0),
+                cache: !matches.is_present("no-cache"),
+                json: matches.value_of("format") == Some("json"),
+            };
+            if !sanitize::sanitize(path, exts, &options) {
+                std::process::exit(1);
+            }
+        }
+        "tests" => {
+            let (sub, matches) = matches.subcommand().unwrap();
+            if sub == "audit" {
+                let dir = Path::new(matches.value_of("dir").unwrap());
+                let input = matches.value_of("in").unwrap();
+                let answer = matches.value_of("out").unwrap();
+                match audit::audit(dir, input, answer) {
+                    Ok(0) => {}
+                    Ok(_) => std::process::exit(1),
+                    Err(err) => {
+                        eprintln!("[Error] {}", err);
+                        eprintln!("\t=> {}", dir.display());
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        "samples" => {
+            let (sub, matches) = matches.subcommand().unwrap();
+            if sub == "check" {
+                let statement = Path::new(matches.value_of("statement").unwrap());
+                let dir = match matches.value_of("dir") {
+                    Some(dir) => Path::new(dir),
+                    None => statement
+                        .parent()
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .unwrap_or_else(|| Path::new(".")),
+                };
+                let validator = matches
+                    .value_of("validator")
+                    .map(|v| executable(Path::new(v), &compiler(matches)));
+                let input = matches.value_of("in").unwrap();
+                let output = matches.value_of("out").unwrap();
+                let result = samples::sample_files(dir, input, output)
+                    .and_then(|files| samples::check(statement, &files, validator.as_deref()));
+                match result {
+                    Ok(0) => {}
+                    Ok(_) => std::process::exit(1),
+                    Err(err) => {
+                        eprintln!("[Error] {}", err);
+                        eprintln!("\t=> {}", statement.display());
+                        std::process::exit(1);
+                    }
+                }
+            }
         }
         "validate" => {
-            let path = matches.value_of("validator").unwrap();
+            let path = executable(
+                Path::new(matches.value_of("validator").unwrap()),
+                &compiler(matches),
+            );
             let paths: Vec<&str> = matches.values_of("paths").unwrap().collect();
-            let filter = matches.value_of("filter");
-            runner::validate(path, paths, filter).unwrap();
+            let filter = regex::Regex::new(matches.value_of("filter").unwrap_or(".*"))
+                .expect("wrong filter format");
+            let mut args = runner::ValidatorArgs::default();
+            for name in ["testset", "group"] {
+                if let Some(value) = matches.value_of(name) {
+                    args.common.push(format!("--{}", name));
+                    args.common.push(value.to_string());
+                }
+            }
+            args.common.extend(
+                matches
+                    .values_of("args")
+                    .into_iter()
+                    .flatten()
+                    .map(String::from),
+            );
+            if let Some(file) = matches.value_of("args-file") {
+                if let Err(err) = args.load(file) {
+                    eprintln!("[Error] {}", err);
+                    eprintln!("\t=> {}", file);
+                    std::process::exit(1);
+                }
+            }
+            let outputs = matches.value_of("outputs").map(|dir| runner::Outputs {
+                dir: Path::new(dir).to_path_buf(),
+                input: matches.value_of("in").unwrap_or(".in").to_string(),
+                answer: matches.value_of("out").unwrap_or(".out").to_string(),
+            });
+            let config = runner::ValidateConfig {
+                filter,
+                args,
+                outputs,
+                coverage: matches.is_present("coverage"),
+                jobs: optional_arg(matches, "jobs").unwrap_or(0),
+                // 0 turns the timeout off
+                timeout: optional_arg(matches, "timeout")
+                    .filter(|&s: &f64| s != 0.0)
+                    .map(|s| {
+                        std::time::Duration::try_from_secs_f64(s).unwrap_or_else(|err| {
+                            eprintln!("[Error] cannot read timeout {:?}: {}", s, err);
+                            std::process::exit(1);
+                        })
+                    }),
+            };
+            if let Err(err) = runner::validate(path, paths, &config) {
+                eprintln!("[Error] {}", err);
+                std::process::exit(1);
+            }
         }
         "eval" => {
             let solution = matches.value_of("solution").unwrap();
@@ -127,6 +426,7 @@ fn main() {
             if !data_dir.is_dir() {
                 panic!("data_dir is not a directory: {}", data_dir.display());
             }
+            let solution = &executable(solution, &compiler(matches));
 
             let time: Option<f32> = matches
                 .value_of("time-limit")
@@ -142,7 +442,21 @@ fn main() {
 
             let limit = Limitation { time, memory_mb };
             let ignore_cr: bool = matches.is_present("loose");
-            runner::eval(solution, data_dir, in_filter, out_filter, &limit, ignore_cr).unwrap();
+            let checker = matches
+                .value_of("eval")
+                .map(|c| executable(Path::new(c), &compiler(matches)));
+            if let Err(err) = runner::eval(
+                solution,
+                data_dir,
+                in_filter,
+                out_filter,
+                &limit,
+                ignore_cr,
+                checker.as_deref(),
+            ) {
+                eprintln!("[Error] {}", err);
+                std::process::exit(1);
+            }
         }
         "new" => {
             // psutil new dir/prob1 --python
@@ -0,0 +1,358 @@
+use crate::generate::{self, write_within, Budget, Range, TestWriter};
+use crate::number;
+use rand::distributions::Uniform;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Write;
+
+pub const MATRIX_MODES: &[&str] = &[
+	"random",
+	"symmetric",
+	"singular",
+	"rank",
+	"permutation",
+	"adjacency",
+];
+
+pub struct Matrix<X> {
+	pub rows: Vec<Vec<X>>,
+}
+
+// r c
+// a11 a12 ..
+impl<X: Display> TestWriter for Matrix<X> {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		let c = self.rows.first().map_or(0, |row| row.len());
+		writeln!(out, "{} {}", self.rows.len(), c)?;
+		for row in &self.rows {
+			let line: Vec<String> = row.iter().map(|x| x.to_string()).collect();
+			writeln!(out, "{}", line.join(" "))?;
+		}
+		Ok(())
+	}
+}
+
+fn check_square(r: usize, c: usize, mode: &str) -> Result<(), Box<dyn Error>> {
+	if r != c {
+		return Err(format!("{} matrix must be square: {} x {}", mode, r, c).into());
+	}
+	Ok(())
+}
+
+pub fn random_matrix<R: Rng>(
+	rng: &mut R,
+	r: usize,
+	c: usize,
+	value_range: &Range<i64>,
+) -> Result<Matrix<i64>, Box<dyn Error>> {
+	if value_range.low > value_range.high {
+		return Err(format!("empty range: [{}, {}]", value_range.low, value_range.high).into());
+	}
+	let dist = Uniform::new_inclusive(value_range.low, value_range.high);
+	Ok(Matrix {
+		rows: (0..r)
+			.map(|_| (0..c).map(|_| rng.sample(dist)).collect())
+			.collect(),
+	})
+}
+
+pub fn symmetric_matrix<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	value_range: &Range<i64>,
+) -> Result<Matrix<i64>, Box<dyn Error>> {
+	let mut a = random_matrix(rng, n, n, value_range)?;
+	for i in 0..n {
+		for j in 0..i {
+			a.rows[i][j] = a.rows[j][i];
+		}
+	}
+	Ok(a)
+}
+
+/// Singular over the rationals: one row (or column) repeats another, then rows and columns are shuffled.
+pub fn singular_matrix<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	value_range: &Range<i64>,
+) -> Result<Matrix<i64>, Box<dyn Error>> {
+	if n < 2 {
+		// a 1x1 matrix is singular only if it is 0
+		if n == 1 && !(value_range.low..=value_range.high).contains(&0) {
+			return Err("1 x 1 singular matrix needs 0 in the value range".into());
+		}
+		return Ok(Matrix {
+			rows: vec![vec![0; n]; n],
+		});
+	}
+	let mut a = random_matrix(rng, n, n, value_range)?;
+	let i = rng.gen_range(0..n);
+	let j = (i + rng.gen_range(1..n)) % n;
+	if rng.gen::<bool>() {
+		a.rows[j] = a.rows[i].clone();
+	} else {
+		for row in &mut a.rows {
+			row[j] = row[i];
+		}
+	}
+
+	a.rows.shuffle(rng);
+	let mut cols: Vec<usize> = (0..n).collect();
+	cols.shuffle(rng);
+	a.rows = a
+		.rows
+		.into_iter()
+		.map(|row| cols.iter().map(|&k| row[k]).collect())
+		.collect();
+	Ok(a)
+}
+
+/// Rank of `a` over GF(p).
+pub fn rank_mod(a: &Matrix<u64>, p: u64) -> usize {
+	let mut a: Vec<Vec<u64>> = a
+		.rows
+		.iter()
+		.map(|row| row.iter().map(|x| x % p).collect())
+		.collect();
+	let c = a.first().map_or(0, |row| row.len());
+	let mut rank = 0;
+	for col in 0..c {
+		let pivot = match (rank..a.len()).find(|&i| a[i][col] != 0) {
+			Some(i) => i,
+			None => continue,
+		};
+		a.swap(rank, pivot);
+		let inv = number::pow_mod(a[rank][col], p - 2, p);
+		let pivot_row = a[rank].clone();
+		for (i, row) in a.iter_mut().enumerate() {
+			if i == rank || row[col] == 0 {
+				continue;
+			}
+			let f = number::mul_mod(row[col], inv, p);
+			for (x, &y) in row.iter_mut().zip(&pivot_row).skip(col) {
+				*x = number::add_mod(*x, p - number::mul_mod(f, y, p), p);
+			}
+		}
+		rank += 1;
+	}
+	rank
+}
+
+/// `r x c` matrix with entries in `[0, p)` and rank exactly `k` over GF(p), built as `B * C`.
+pub fn rank_matrix<R: Rng>(
+	rng: &mut R,
+	r: usize,
+	c: usize,
+	k: usize,
+	p: u64,
+) -> Result<Matrix<u64>, Box<dyn Error>> {
+	if !number::is_prime(p) {
+		return Err(format!("modulus must be prime: {}", p).into());
+	}
+	if k > std::cmp::min(r, c) {
+		return Err(format!("rank {} is impossible for {} x {}", k, r, c).into());
+	}
+	loop {
+		let b: Vec<Vec<u64>> = (0..r)
+			.map(|_| (0..k).map(|_| rng.gen_range(0..p)).collect())
+			.collect();
+		let m: Vec<Vec<u64>> = (0..k)
+			.map(|_| (0..c).map(|_| rng.gen_range(0..p)).collect())
+			.collect();
+		let rows = (0..r)
+			.map(|i| {
+				(0..c)
+					.map(|j| {
+						(0..k).fold(0, |acc, t| {
+							number::add_mod(acc, number::mul_mod(b[i][t], m[t][j], p), p)
+						})
+					})
+					.collect()
+			})
+			.collect();
+		let a = Matrix { rows };
+		// retry until both random factors have full rank k
+		if rank_mod(&a, p) == k {
+			return Ok(a);
+		}
+	}
+}
+
+pub fn permutation_matrix<R: Rng>(rng: &mut R, n: usize) -> Matrix<u8> {
+	let p = generate::permutation(rng, n);
+	Matrix {
+		rows: p
+			.into_iter()
+			.map(|v| (1..n + 1).map(|j| (j == v) as u8).collect())
+			.collect(),
+	}
+}
+
+/// 0/1 adjacency matrix of `generate::random_graph`.
+pub fn adjacency_matrix<R: Rng>(
+	rng: &mut R,
+	n: usize,
+	m: usize,
+	connected: bool,
+	directed: bool,
+) -> Result<Matrix<u8>, Box<dyn Error>> {
+	let g = generate::random_graph::<i8, R>(rng, n, m, connected, directed, None)?;
+	let mut rows = vec![vec![0u8; n]; n];
+	for (u, v) in g.edges {
+		rows[u - 1][v - 1] = 1;
+		if !directed {
+			rows[v - 1][u - 1] = 1;
+		}
+	}
+	Ok(Matrix { rows })
+}
+
+pub struct MatrixOptions {
+	pub value_range: Option<Range<i64>>,
+	pub rank: Option<usize>,
+	pub modulus: Option<u64>,
+	pub edges: Option<usize>,
+	pub connected: bool,
+	pub directed: bool,
+}
+
+pub fn generate_matrix(
+	mode: &str,
+	r: usize,
+	c: usize,
+	opt: &MatrixOptions,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let mut rng = rand::thread_rng();
+	let value_range = || {
+		opt.value_range
+			.as_ref()
+			.ok_or_else(|| format!("{} matrix needs a value range (-i min max)", mode))
+	};
+	// the other modes choose their own values
+	let no_value_range = || match &opt.value_range {
+		Some(_) => Err(format!("{} matrix takes no value range (-i)", mode)),
+		None => Ok(()),
+	};
+	match mode {
+		"random" => {
+			let value_range = value_range()?;
+			write_within(budget, |s| {
+				let (r, c) = (s.side(r, 1), s.side(c, 1));
+				random_matrix(&mut rng, r, c, &s.range(value_range))
+			})
+		}
+		"symmetric" => {
+			check_square(r, c, mode)?;
+			let value_range = value_range()?;
+			write_within(budget, |s| {
+				symmetric_matrix(&mut rng, s.side(r, 1), &s.range(value_range))
+			})
+		}
+		"singular" => {
+			check_square(r, c, mode)?;
+			let value_range = value_range()?;
+			write_within(budget, |s| {
+				singular_matrix(&mut rng, s.side(r, 2), &s.range(value_range))
+			})
+		}
+		"rank" => {
+			let k = opt.rank.ok_or("rank matrix needs --rank")?;
+			let p = opt.modulus.ok_or("rank matrix needs --mod")?;
+			no_value_range()?;
+			write_within(budget, |s| {
+				let (r, c) = (s.side(r, k), s.side(c, k));
+				rank_matrix(&mut rng, r, c, k, p)
+			})
+		}
+		"permutation" => {
+			check_square(r, c, mode)?;
+			no_value_range()?;
+			write_within(budget, |s| Ok(permutation_matrix(&mut rng, s.side(r, 1))))
+		}
+		"adjacency" => {
+			check_square(r, c, mode)?;
+			let m = opt.edges.ok_or("adjacency matrix needs --edges")?;
+			no_value_range()?;
+			write_within(budget, |s| {
+				let n = s.side(r, 1);
+				// keep the density
+				let max_m = if opt.directed {
+					n * n.saturating_sub(1)
+				} else {
+					n * n.saturating_sub(1) / 2
+				};
+				let min_m = if opt.connected {
+					n.saturating_sub(1)
+				} else {
+					0
+				};
+				let m = std::cmp::min(s.count(m, min_m), max_m);
+				adjacency_matrix(&mut rng, n, m, opt.connected, opt.directed)
+			})
+		}
+		_ => Err(format!("unknown mode: {}", mode).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matrix(rows: &[&[u64]]) -> Matrix<u64> {
+		Matrix {
+			rows: rows.iter().map(|row| row.to_vec()).collect(),
+		}
+	}
+
+	#[test]
+	fn rank_modulo_p() {
+		let a = matrix(&[&[1, 2, 3], &[4, 5, 6], &[7, 8, 10]]);
+		assert_eq!(rank_mod(&a, 1_000_000_007), 3);
+		// determinant -3
+		assert_eq!(rank_mod(&a, 3), 2);
+		assert_eq!(rank_mod(&matrix(&[&[2, 4], &[1, 2]]), 7), 1);
+		assert_eq!(rank_mod(&matrix(&[&[0, 0], &[0, 0]]), 5), 0);
+		assert_eq!(rank_mod(&matrix(&[&[1, 2, 3, 4]]), 2), 1);
+	}
+
+	#[test]
+	fn rank_matrix_has_rank_k() {
+		let mut rng = rand::thread_rng();
+		for p in [2, 998244353, 18446744073709551557] {
+			for k in 0..=3 {
+				let a = rank_matrix(&mut rng, 3, 5, k, p).unwrap();
+				assert_eq!(rank_mod(&a, p), k);
+				assert!(a.rows.iter().flatten().all(|&x| x < p));
+			}
+		}
+		assert!(rank_matrix(&mut rng, 3, 5, 4, 7).is_err());
+		assert!(rank_matrix(&mut rng, 3, 5, 2, 8).is_err());
+	}
+
+	#[test]
+	fn singular_modulo_p() {
+		let mut rng = rand::thread_rng();
+		let p = 1_000_000_007;
+		for n in 2..8 {
+			let a = singular_matrix(&mut rng, n, &Range { low: -5, high: 5 }).unwrap();
+			let a = Matrix {
+				rows: a
+					.rows
+					.iter()
+					.map(|row| row.iter().map(|&x| x.rem_euclid(p as i64) as u64).collect())
+					.collect(),
+			};
+			assert!(rank_mod(&a, p) < n);
+		}
+	}
+
+	#[test]
+	fn empty_value_range() {
+		let mut rng = rand::thread_rng();
+		assert!(random_matrix(&mut rng, 2, 2, &Range { low: 5, high: 1 }).is_err());
+		assert!(random_matrix(&mut rng, 2, 2, &Range { low: 1, high: 1 }).is_ok());
+	}
+}
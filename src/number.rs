@@ -0,0 +1,497 @@
+use crate::generate::{write_within, Budget, TestWriter};
+use rand::Rng;
+use std::error::Error;
+use std::io::Write;
+
+pub const NUMBER_MODES: &[&str] = &[
+	"primes",
+	"largest-primes",
+	"highly-composite",
+	"carmichael",
+	"strong-pseudoprimes",
+	"semiprimes",
+	"gcd-pairs",
+];
+
+// odd numbers tried when searching downward for rare composites
+const SCAN_BUDGET: u64 = 5_000_000;
+
+// smallest strong pseudoprime to all of the first k prime bases (OEIS A014233)
+const PSI: &[u64] = &[
+	2047,
+	1373653,
+	25326001,
+	3215031751,
+	2152302898747,
+	3474749660383,
+	341550071728321,
+	3825123056546413051,
+];
+
+pub fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+	((a as u128 + b as u128) % m as u128) as u64
+}
+
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+	(a as u128 * b as u128 % m as u128) as u64
+}
+
+pub fn pow_mod(mut a: u64, mut e: u64, m: u64) -> u64 {
+	let mut r = 1 % m;
+	a %= m;
+	while e > 0 {
+		if e & 1 == 1 {
+			r = mul_mod(r, a, m);
+		}
+		a = mul_mod(a, a, m);
+		e >>= 1;
+	}
+	r
+}
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+	while b != 0 {
+		let t = a % b;
+		a = b;
+		b = t;
+	}
+	a
+}
+
+/// Miller-Rabin round: is odd `n > 2` a strong probable prime to base `a`?
+pub fn is_strong_probable_prime(n: u64, a: u64) -> bool {
+	let a = a % n;
+	if a == 0 {
+		return true;
+	}
+	let s = (n - 1).trailing_zeros();
+	let d = (n - 1) >> s;
+	let mut x = pow_mod(a, d, n);
+	if x == 1 || x == n - 1 {
+		return true;
+	}
+	for _ in 1..s {
+		x = mul_mod(x, x, n);
+		if x == n - 1 {
+			return true;
+		}
+	}
+	false
+}
+
+/// Deterministic for every `u64`.
+pub fn is_prime(n: u64) -> bool {
+	if n < 2 {
+		return false;
+	}
+	for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+		if n.is_multiple_of(p) {
+			return n == p;
+		}
+	}
+	[2, 325, 9375, 28178, 450775, 9780504, 1795265022]
+		.iter()
+		.all(|&a| is_strong_probable_prime(n, a))
+}
+
+// Floyd cycle detection, c = 1, 2, .. keeps it deterministic
+fn pollard_rho(n: u64) -> u64 {
+	if n.is_multiple_of(2) {
+		return 2;
+	}
+	for c in 1u64.. {
+		let f = |x: u64| ((mul_mod(x, x, n) as u128 + c as u128) % n as u128) as u64;
+		let (mut x, mut y, mut d) = (2, 2, 1);
+		while d == 1 {
+			x = f(x);
+			y = f(f(y));
+			d = gcd(x.abs_diff(y), n);
+		}
+		if d != n {
+			return d;
+		}
+	}
+	unreachable!()
+}
+
+/// Prime factors with multiplicity, sorted.
+pub fn factorize(n: u64) -> Vec<u64> {
+	let mut factors = Vec::new();
+	let mut n = n;
+	for p in [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37] {
+		while n.is_multiple_of(p) {
+			factors.push(p);
+			n /= p;
+		}
+	}
+	let mut stack = vec![n];
+	while let Some(m) = stack.pop() {
+		if m == 1 {
+			continue;
+		}
+		if is_prime(m) {
+			factors.push(m);
+			continue;
+		}
+		let d = pollard_rho(m);
+		stack.push(d);
+		stack.push(m / d);
+	}
+	factors.sort_unstable();
+	factors
+}
+
+/// Korselt: squarefree, at least two prime factors, `p - 1 | n - 1` for every `p | n`.
+pub fn is_carmichael(n: u64) -> bool {
+	if n < 3 || n.is_multiple_of(2) || is_prime(n) {
+		return false;
+	}
+	let factors = factorize(n);
+	factors.windows(2).all(|w| w[0] != w[1])
+		&& factors.iter().all(|&p| (n - 1).is_multiple_of(p - 1))
+}
+
+/// Composite, and a strong probable prime to every base.
+pub fn is_strong_pseudoprime(n: u64, bases: &[u64]) -> bool {
+	n > 2 && n % 2 == 1 && bases.iter().all(|&a| is_strong_probable_prime(n, a)) && !is_prime(n)
+}
+
+/// `count` uniformly random primes in `[2, bound]`.
+pub fn random_primes<R: Rng>(
+	rng: &mut R,
+	count: usize,
+	bound: u64,
+) -> Result<Vec<u64>, Box<dyn Error>> {
+	if bound < 2 {
+		return Err(format!("no prime <= {}", bound).into());
+	}
+	Ok((0..count)
+		.map(|_| loop {
+			let x = rng.gen_range(2..=bound);
+			if is_prime(x) {
+				break x;
+			}
+		})
+		.collect())
+}
+
+/// The `count` largest primes `<= bound`, descending.
+pub fn largest_primes(count: usize, bound: u64) -> Vec<u64> {
+	(2..=bound)
+		.rev()
+		.filter(|&x| is_prime(x))
+		.take(count)
+		.collect()
+}
+
+/// The `count` largest highly composite numbers `<= bound` (more divisors than any smaller number),
+/// descending. The first one has the most divisors among `1..=bound`.
+pub fn highly_composite(count: usize, bound: u64) -> Vec<u64> {
+	const PRIMES: [u64; 15] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+
+	// exponents are non-increasing along the primes
+	fn dfs(i: usize, n: u64, divisors: u64, max_e: u32, bound: u64, out: &mut Vec<(u64, u64)>) {
+		out.push((n, divisors));
+		if i == PRIMES.len() {
+			return;
+		}
+		let mut m = n;
+		for e in 1..=max_e {
+			m = match m.checked_mul(PRIMES[i]) {
+				Some(m) if m <= bound => m,
+				_ => break,
+			};
+			dfs(i + 1, m, divisors * (e as u64 + 1), e, bound, out);
+		}
+	}
+
+	if bound == 0 {
+		return Vec::new();
+	}
+	let mut candidates = Vec::new();
+	dfs(0, 1, 1, 64, bound, &mut candidates);
+	candidates.sort_unstable();
+
+	let mut records = Vec::new();
+	let mut best = 0;
+	for (n, d) in candidates {
+		if d > best {
+			best = d;
+			records.push(n);
+		}
+	}
+	records.into_iter().rev().take(count).collect()
+}
+
+// (6k+1)(12k+1)(18k+1) is a Carmichael number when all three factors are prime
+fn chernick(bound: u64) -> Vec<u64> {
+	let mut v = Vec::new();
+	let mut k: u64 = 1;
+	loop {
+		let (a, b, c) = (6 * k + 1, 12 * k + 1, 18 * k + 1);
+		let n = match a.checked_mul(b).and_then(|ab| ab.checked_mul(c)) {
+			Some(n) if n <= bound => n,
+			_ => break,
+		};
+		if is_prime(a) && is_prime(b) && is_prime(c) {
+			v.push(n);
+		}
+		k += 1;
+	}
+	v
+}
+
+// largest odd n <= bound with pred(n), within SCAN_BUDGET candidates, and the last candidate
+// when the budget ran out first
+fn scan_down<F: Fn(u64) -> bool>(count: usize, bound: u64, pred: F) -> (Vec<u64>, Option<u64>) {
+	let start = if bound.is_multiple_of(2) {
+		bound.saturating_sub(1)
+	} else {
+		bound
+	};
+	let mut v = Vec::new();
+	let mut n = start;
+	let mut tried = 0;
+	while n >= 3 && v.len() < count && tried < SCAN_BUDGET {
+		if pred(n) {
+			v.push(n);
+		}
+		n -= 2;
+		tried += 1;
+	}
+	let gave_up = tried == SCAN_BUDGET && n >= 3 && v.len() < count;
+	(v, if gave_up { Some(n + 2) } else { None })
+}
+
+fn largest_unique(mut v: Vec<u64>, count: usize) -> Vec<u64> {
+	v.sort_unstable_by(|a, b| b.cmp(a));
+	v.dedup();
+	v.truncate(count);
+	v
+}
+
+/// Up to `count` Carmichael numbers `<= bound`, largest first, and where the scan gave up.
+///
+/// Best effort: scans downward from `bound` (Fermat base 2 filter, then Korselt) through at most
+/// `SCAN_BUDGET` odd numbers and adds Chernick numbers, which reach far beyond the scan. When the
+/// scan gives up at `Some(n)`, the values below `n` are only Chernick numbers, not the largest.
+pub fn carmichael(count: usize, bound: u64) -> (Vec<u64>, Option<u64>) {
+	let (mut v, gave_up) = scan_down(count, bound, |n| {
+		pow_mod(2, n - 1, n) == 1 && is_carmichael(n)
+	});
+	v.extend(chernick(bound));
+	(largest_unique(v, count), gave_up)
+}
+
+/// Up to `count` composites `<= bound` that pass Miller-Rabin for every base in `bases`,
+/// largest first, and where the scan gave up.
+///
+/// Best effort like `carmichael`: below where the scan gives up, there are only the smallest known
+/// ones for the first k prime bases and the Chernick numbers that pass, not the largest. These are
+/// rare for several bases, and none below `2^64` passes all twelve prime bases up to 37.
+pub fn strong_pseudoprimes(count: usize, bound: u64, bases: &[u64]) -> (Vec<u64>, Option<u64>) {
+	let (mut v, gave_up) = scan_down(count, bound, |n| is_strong_pseudoprime(n, bases));
+	let known = PSI.iter().cloned().chain(chernick(bound));
+	v.extend(known.filter(|&n| n <= bound && is_strong_pseudoprime(n, bases)));
+	(largest_unique(v, count), gave_up)
+}
+
+fn random_prime_in<R: Rng>(rng: &mut R, low: u64, high: u64) -> Option<u64> {
+	if low > high {
+		return None;
+	}
+	// random probes first, a linear scan only for tiny ranges
+	for _ in 0..100_000 {
+		let x = rng.gen_range(low..=high);
+		if is_prime(x) {
+			return Some(x);
+		}
+	}
+	(low..=high).find(|&x| is_prime(x))
+}
+
+/// `count` products `p * q <= bound` of two primes with `sqrt(bound) / 2 <= p <= q`.
+pub fn semiprimes<R: Rng>(
+	rng: &mut R,
+	count: usize,
+	bound: u64,
+) -> Result<Vec<u64>, Box<dyn Error>> {
+	// exact, a rounded f64 root can exceed bound / root
+	let root = bound.isqrt();
+	let mut v = Vec::with_capacity(count);
+	for _ in 0..count {
+		let p = random_prime_in(rng, std::cmp::max(2, root / 2), root)
+			.ok_or_else(|| format!("no semiprime with two large factors <= {}", bound))?;
+		let q = random_prime_in(rng, p, bound / p)
+			.ok_or_else(|| format!("no semiprime with two large factors <= {}", bound))?;
+		v.push(p * q);
+	}
+	Ok(v)
+}
+
+/// `count` pairs `(g * x, g * y) <= bound` with `g >= bound / 16` and coprime `x != y`.
+pub fn gcd_pairs<R: Rng>(
+	rng: &mut R,
+	count: usize,
+	bound: u64,
+) -> Result<Vec<(u64, u64)>, Box<dyn Error>> {
+	if bound < 2 {
+		return Err(format!("no distinct pair <= {}", bound).into());
+	}
+	let mut v = Vec::with_capacity(count);
+	while v.len() < count {
+		let g = rng.gen_range(std::cmp::max(1, bound / 16)..=bound / 2);
+		let k = bound / g;
+		let x = rng.gen_range(1..=k);
+		let y = rng.gen_range(1..=k);
+		if x != y && gcd(x, y) == 1 {
+			v.push((g * x, g * y));
+		}
+	}
+	Ok(v)
+}
+
+pub enum Numbers {
+	Values(Vec<u64>),
+	Pairs(Vec<(u64, u64)>),
+}
+
+// one value (or pair) per line
+impl TestWriter for Numbers {
+	fn write_to<O: Write>(&self, out: &mut O) -> std::io::Result<()> {
+		match self {
+			Numbers::Values(values) => {
+				for x in values {
+					writeln!(out, "{}", x)?;
+				}
+			}
+			Numbers::Pairs(pairs) => {
+				for (a, b) in pairs {
+					writeln!(out, "{} {}", a, b)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+pub fn numbers<R: Rng>(
+	rng: &mut R,
+	mode: &str,
+	count: usize,
+	bound: u64,
+	bases: &[u64],
+) -> Result<Numbers, Box<dyn Error>> {
+	let (values, gave_up) = match mode {
+		"primes" => (random_primes(rng, count, bound)?, None),
+		"largest-primes" => (largest_primes(count, bound), None),
+		"highly-composite" => (highly_composite(count, bound), None),
+		"carmichael" => carmichael(count, bound),
+		"strong-pseudoprimes" => strong_pseudoprimes(count, bound, bases),
+		"semiprimes" => (semiprimes(rng, count, bound)?, None),
+		"gcd-pairs" => return Ok(Numbers::Pairs(gcd_pairs(rng, count, bound)?)),
+		_ => return Err(format!("unknown mode: {}", mode).into()),
+	};
+	if let Some(n) = gave_up {
+		eprintln!(
+			"searched down to {} only, the values below are known ones, not the largest",
+			n
+		);
+	}
+	if values.len() < count {
+		eprintln!("only {} found <= {}", values.len(), bound);
+	}
+	Ok(Numbers::Values(values))
+}
+
+pub fn generate_numbers(
+	mode: &str,
+	count: usize,
+	bound: u64,
+	bases: &[u64],
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let mut rng = rand::thread_rng();
+	write_within(budget, |s| {
+		numbers(&mut rng, mode, s.count(count, 1), bound, bases)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn miller_rabin_rounds() {
+		// 2047 = 23 * 89 fools base 2 but not base 3
+		assert!(is_strong_probable_prime(2047, 2));
+		assert!(!is_strong_probable_prime(2047, 3));
+		assert!(is_strong_pseudoprime(3215031751, &[2, 3, 5, 7]));
+		assert!(!is_strong_pseudoprime(3215031751, &[2, 3, 5, 7, 11]));
+		assert!(!is_strong_pseudoprime(3215031751 + 2, &[2, 3, 5, 7]));
+	}
+
+	#[test]
+	fn small_primes() {
+		let primes: Vec<u64> = (0..50).filter(|&n| is_prime(n)).collect();
+		assert_eq!(
+			primes,
+			[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]
+		);
+	}
+
+	#[test]
+	fn primes_near_u64_max() {
+		let top: Vec<u64> = (u64::MAX - 100..=u64::MAX)
+			.rev()
+			.filter(|&n| is_prime(n))
+			.collect();
+		assert_eq!(
+			top,
+			[
+				18446744073709551557,
+				18446744073709551533,
+				18446744073709551521
+			]
+		);
+		assert_eq!(largest_primes(1, u64::MAX), [18446744073709551557]);
+	}
+
+	#[test]
+	fn carmichael_numbers() {
+		let known = [561, 1105, 1729, 2465, 2821, 6601, 8911, 10585, 15841, 29341];
+		for n in known {
+			assert!(is_carmichael(n), "{}", n);
+			assert_eq!(pow_mod(2, n - 1, n), 1);
+		}
+		let found: Vec<u64> = (1..30000).filter(|&n| is_carmichael(n)).collect();
+		assert_eq!(found, known);
+		let (largest, gave_up) = carmichael(3, 10000);
+		assert_eq!(largest, [8911, 6601, 2821]);
+		assert_eq!(gave_up, None);
+	}
+
+	#[test]
+	fn factorization() {
+		assert_eq!(factorize(1), Vec::<u64>::new());
+		assert_eq!(factorize(561), [3, 11, 17]);
+		assert_eq!(factorize(1 << 40), vec![2; 40]);
+		assert_eq!(factorize(u64::MAX), [3, 5, 17, 257, 641, 65537, 6700417]);
+		assert_eq!(factorize(4294967279 * 4294967291), [4294967279, 4294967291]);
+		assert_eq!(factorize(4294967291 * 4294967291), [4294967291, 4294967291]);
+		assert_eq!(factorize(18446744073709551557), [18446744073709551557]);
+	}
+
+	#[test]
+	fn semiprimes_fit_the_bound() {
+		let mut rng = rand::thread_rng();
+		// a rounded f64 root would be 4294967291 here
+		let bound = 4294967291 * 4294967291 - 1;
+		for n in semiprimes(&mut rng, 20, bound).unwrap() {
+			assert!(n <= bound);
+			let factors = factorize(n);
+			assert_eq!(factors.len(), 2);
+			assert!(factors[0] >= bound.isqrt() / 2);
+		}
+	}
+}
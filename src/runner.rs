@@ -1,11 +1,15 @@
+use crate::audit;
+use crate::coverage::Coverage;
+use crate::location::Locator;
 use crate::sandbox;
 use crate::sandbox::Limitation;
 use colored::*;
+use rayon::prelude::*;
 use std::fs::File;
 use std::io::Read;
-use std::os::unix::process::CommandExt;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::time::Duration;
 use wait_timeout::ChildExt;
 use walkdir::WalkDir;
@@ -16,111 +20,589 @@ error_chain! {
     foreign_links {
         IoError(std::io::Error);
     }
+    errors {
+        TimedOut(seconds: f64) {
+            description("timed out")
+            display("timed out after {}s", seconds)
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum JudgeResult {
     Correct,
     WrongAnswer,
     TimeOver,
     MemoryOver,
     RuntimeError,
+    // from a checker
+    PresentationError,
+    JudgeFailure,
+    PartialPoints(f64),
 }
 
 pub struct EvalConfig<'a> {
-    cmd: &'a str,
-    args: Vec<String>,
-    limit: &'a Limitation,
-    ignore_cr: bool,
+    pub cmd: &'a str,
+    pub args: Vec<String>,
+    pub limit: &'a Limitation,
+    pub ignore_cr: bool,
+    /// Judges the output instead of comparing bytes: `checker input output answer`, testlib exit codes.
+    pub checker: Option<&'a Path>,
+}
+
+impl<'a> EvalConfig<'a> {
+    /// `Main.class` runs as `java -cp <dir> Main`, anything else is executed directly.
+    pub fn for_solution(
+        solution: &'a Path,
+        limit: &'a Limitation,
+        ignore_cr: bool,
+    ) -> Result<EvalConfig<'a>> {
+        if !solution.is_file() {
+            return Err("solution file does not exist")?;
+        }
+
+        let is_java = {
+            let ext: Option<&str> = solution.extension().and_then(|s| s.to_str());
+            ext.is_some() && ext.unwrap() == "class"
+        };
+
+        let (cmd, args) = {
+            if is_java {
+                let cp = solution
+                    .parent()
+                    .and_then(|p| p.to_str())
+                    .unwrap_or(".")
+                    .to_string();
+                let stem = solution.file_stem().unwrap().to_str().unwrap().to_string();
+                ("java", ["-cp".to_string(), cp, stem].to_vec())
+            } else {
+                (solution.to_str().unwrap(), Vec::new())
+            }
+        };
+        Ok(EvalConfig {
+            cmd,
+            args,
+            limit,
+            ignore_cr,
+            checker: None,
+        })
+    }
+}
+
+/// Validator arguments: `common` for every file, then those of the first `mapped` pattern
+/// that matches the file's path (relative to the directory given to `validate`).
+#[derive(Default)]
+pub struct ValidatorArgs {
+    pub common: Vec<String>,
+    pub mapped: Vec<(regex::Regex, Vec<String>)>,
+}
+
+impl ValidatorArgs {
+    /// `pattern = args` lines, `#` starts a comment:
+    ///
+    /// ```text
+    /// ^small/ = --testset small --group 1
+    /// ^large/ = --testset large --group 2 --max-n 200000
+    /// ```
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let text = std::fs::read_to_string(path).chain_err(|| "cannot read the argument file")?;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (pattern, args) = match line.split_once('=') {
+                Some(pair) => pair,
+                None => return Err(format!("line {}: expected `pattern = args`", i + 1).into()),
+            };
+            let pattern = regex::Regex::new(pattern.trim())
+                .chain_err(|| format!("line {}: bad pattern", i + 1))?;
+            let args = args.split_whitespace().map(String::from).collect();
+            self.mapped.push((pattern, args));
+        }
+        Ok(())
+    }
+
+    pub fn for_file(&self, relative: &Path) -> Vec<String> {
+        let name = relative.to_string_lossy();
+        let mut args = self.common.clone();
+        if let Some((_, mapped)) = self.mapped.iter().find(|(p, _)| p.is_match(&name)) {
+            args.extend(mapped.iter().cloned());
+        }
+        args
+    }
+}
+
+/// Waits at most `time` for `child` and kills it after that: `None` if it timed out.
+fn wait_or_kill(child: &mut Child, time: Duration) -> Result<Option<ExitStatus>> {
+    match child.wait_timeout(time)? {
+        Some(status) => Ok(Some(status)),
+        None => {
+            child.kill()?;
+            child.wait()?;
+            Ok(None)
+        }
+    }
+}
+
+// runs `command` to the end with its stderr, `ErrorKind::TimedOut` after `timeout`
+fn run_with_stderr(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> Result<(ExitStatus, Vec<u8>)> {
+    let mut child = command.stderr(Stdio::piped()).spawn()?;
+    // read while it runs, it blocks once the pipe is full
+    let mut stderr = child.stderr.take().unwrap();
+    let reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+    let status = match timeout {
+        Some(time) => wait_or_kill(&mut child, time)?,
+        None => Some(child.wait()?),
+    };
+    let stderr = reader.join().unwrap_or_default();
+    match (status, timeout) {
+        (Some(status), _) => Ok((status, stderr)),
+        (None, Some(time)) => Err(ErrorKind::TimedOut(time.as_secs_f64()).into()),
+        (None, None) => unreachable!(),
+    }
 }
 
-pub fn validate_data<P1: AsRef<Path>, P2: AsRef<Path>>(exe_path: P1, data_path: P2) -> Result<()> {
+pub fn validate_data<P1: AsRef<Path>, P2: AsRef<Path>>(
+    exe_path: P1,
+    args: &[String],
+    data_path: P2,
+    timeout: Option<Duration>,
+) -> Result<()> {
     let data_path = data_path.as_ref();
-    let f: File = File::open(&data_path).expect("fail to open file");
+    let f: File = File::open(&data_path).chain_err(|| "fail to open data file")?;
 
-    let status = unsafe {
+    let (status, stderr) = run_with_stderr(
         Command::new(exe_path.as_ref())
+            .args(args)
             .stdin(f)
-            .pre_exec(|| Ok(()))
-            .status()?
-    };
+            .stdout(Stdio::null()),
+        timeout,
+    )?;
 
     if status.success() {
-        Ok(())
+        return Ok(());
+    }
+    // testlib ends its message with "(stdin, line 3)"
+    let message = String::from_utf8_lossy(&stderr);
+    let line = regex::Regex::new(r"line (\d+)")
+        .unwrap()
+        .captures(&message)
+        .and_then(|c| c[1].parse::<usize>().ok());
+    let mut report = format!("validation failed ({})", describe_status(status));
+    if let Some(line) = line {
+        let data = std::fs::read(data_path)?;
+        let snippet = Locator::new(&data).line_snippet(line);
+        report.push_str(&format!(" at line {}: {}", line, snippet));
+    }
+    report.push_str(&indent_message(&message));
+    Err(report.into())
+}
+
+// lines of a validator's message shown, and bytes of each
+const MESSAGE_LINES: usize = 5;
+const MESSAGE_WIDTH: usize = 200;
+
+/// `exit code 3`, or `killed by SIGSEGV` for a signal.
+pub fn describe_status(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+    let signal = status.signal().unwrap_or(0);
+    let name = match signal {
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        _ => return format!("killed by signal {}", signal),
+    };
+    format!("killed by {}", name)
+}
+
+// at most `MESSAGE_WIDTH` bytes
+fn cut_line(line: &str) -> String {
+    let mut end = std::cmp::min(line.len(), MESSAGE_WIDTH);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end < line.len() {
+        format!("{} ..", &line[..end])
     } else {
-        Err("validation failed".into())
+        line.to_string()
     }
 }
 
-pub fn validate<P: AsRef<Path>>(exe_path: P, paths: Vec<&str>, filter: Option<&str>) -> Result<()> {
-    let exe_path = exe_path.as_ref();
-    println!("{}", "Validating ..".green());
+// "\n\t" before each of the first lines, cut to `MESSAGE_WIDTH`
+fn indent_message(message: &str) -> String {
+    let lines: Vec<&str> = message
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let mut s = String::new();
+    for line in lines.iter().take(MESSAGE_LINES) {
+        s.push_str("\n\t");
+        s.push_str(&cut_line(line));
+    }
+    if lines.len() > MESSAGE_LINES {
+        s.push_str(&format!(
+            "\n\t.. {} more lines",
+            lines.len() - MESSAGE_LINES
+        ));
+    }
+    s
+}
 
-    let filter = regex::Regex::new(filter.unwrap_or(".*")).expect("wrong filter format");
-    let mut good = 0;
-    let mut error = 0;
+/// Checks an output instead of an input: runs the validator as `validator input output answer`,
+/// the testlib checker arguments. testlib takes no validator arguments after them.
+pub fn validate_output<P: AsRef<Path>>(
+    exe_path: P,
+    input: &Path,
+    output: &Path,
+    answer: &Path,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let (status, stderr) = run_with_stderr(
+        Command::new(exe_path.as_ref())
+            .arg(input)
+            .arg(output)
+            .arg(answer)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null()),
+        timeout,
+    )?;
+    if status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&stderr);
+    Err(format!(
+        "output validation failed ({}){}",
+        describe_status(status),
+        indent_message(&message)
+    )
+    .into())
+}
 
+/// Outputs to check with `validate_output`, each with the input and answer of its test.
+pub struct Outputs {
+    /// The outputs, at the relative path of their answers (the data directory for the answers themselves).
+    pub dir: PathBuf,
+    /// Parts of the input and answer names (`.in`, `.out`), paired like `tests audit`.
+    pub input: String,
+    pub answer: String,
+}
+
+pub struct ValidateConfig {
+    /// On file names, of the inputs when checking outputs.
+    pub filter: regex::Regex,
+    pub args: ValidatorArgs,
+    pub outputs: Option<Outputs>,
+    /// Collect testlib's test overview logs of the valid tests and print which bounds they reach.
+    pub coverage: bool,
+    /// Validator runs at once, 0 for one per CPU.
+    pub jobs: usize,
+    /// A validator still running after this is killed, the file counts as timed out.
+    pub timeout: Option<Duration>,
+}
+
+// one run of the validator
+struct Check {
+    input: PathBuf,
+    // and the answer
+    output: Option<(PathBuf, PathBuf)>,
+    // the checked file as shown in errors
+    shown: PathBuf,
+    // the input as matched by `ValidatorArgs`
+    key: PathBuf,
+}
+
+// a test without its answer or output: the path shown and the error
+type Missing = (PathBuf, &'static str);
+
+// every file under `paths` to check, and the tests missing a file, shown like the checks
+fn collect_checks(paths: &[&str], config: &ValidateConfig) -> Result<(Vec<Check>, Vec<Missing>)> {
+    let mut checks = Vec::new();
+    let mut missing = Vec::new();
+    let name_matches = |path: &Path| {
+        config
+            .filter
+            .is_match(&path.file_name().unwrap().to_string_lossy())
+    };
     for path in paths {
         let path: &Path = path.as_ref();
         if path.is_file() {
-            if !filter.is_match(path.file_name().unwrap().to_str().unwrap()) {
+            if !name_matches(path) {
                 continue;
             }
-
-            match validate_data(exe_path, path) {
-                Ok(()) => {
-                    good += 1;
+            let output = match &config.outputs {
+                Some(outputs) => {
+                    let name = path.file_name().unwrap().to_string_lossy();
+                    let answer_name = name.replacen(outputs.input.as_str(), &outputs.answer, 1);
+                    let answer = path.with_file_name(&answer_name);
+                    let output = outputs.dir.join(&answer_name);
+                    if !name.contains(outputs.input.as_str()) || !answer.is_file() {
+                        missing.push((path.to_path_buf(), "no answer"));
+                        continue;
+                    }
+                    if !output.is_file() {
+                        missing.push((output, "no output"));
+                        continue;
+                    }
+                    Some((output, answer))
                 }
-                Err(err) => {
-                    error += 1;
-                    eprintln!("[Error] {}", err);
-                    eprintln!("\t=> {}", path.display());
+                None => None,
+            };
+            checks.push(Check {
+                input: path.to_path_buf(),
+                shown: output
+                    .as_ref()
+                    .map_or_else(|| path.to_path_buf(), |(output, _)| output.clone()),
+                output,
+                key: PathBuf::from(path.file_name().unwrap()),
+            });
+            continue;
+        }
+
+        let dir = path;
+        let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+        match &config.outputs {
+            Some(outputs) => {
+                let pairing =
+                    audit::pair(dir, &outputs.input, &outputs.answer).map_err(|e| e.to_string())?;
+                for (input, answer) in pairing.pairs {
+                    if !name_matches(&input) {
+                        continue;
+                    }
+                    let output = outputs.dir.join(relative(&answer));
+                    if !output.is_file() {
+                        missing.push((output, "no output"));
+                        continue;
+                    }
+                    checks.push(Check {
+                        shown: output.clone(),
+                        key: relative(&input),
+                        input,
+                        output: Some((output, answer)),
+                    });
                 }
+                missing.extend(
+                    pairing
+                        .orphan_inputs
+                        .iter()
+                        .filter(|p| name_matches(p))
+                        .map(|p| (relative(p), "no answer")),
+                );
             }
-        } else {
-            let dir = path;
-            for entry in WalkDir::new(dir).into_iter() {
-                let entry = entry.expect("fail to list dir");
-                let path = entry.path();
-                if !filter.is_match(path.file_name().unwrap().to_str().unwrap()) {
-                    continue;
+            None => {
+                for entry in WalkDir::new(dir).sort_by_file_name() {
+                    let entry = entry.expect("fail to list dir");
+                    let path = entry.path();
+                    if !entry.file_type().is_file() || !name_matches(path) {
+                        continue;
+                    }
+                    checks.push(Check {
+                        input: path.to_path_buf(),
+                        output: None,
+                        shown: relative(path),
+                        key: relative(path),
+                    });
                 }
+            }
+        }
+    }
+    Ok((checks, missing))
+}
 
-                match validate_data(exe_path, path) {
-                    Ok(()) => {
-                        good += 1;
-                    }
-                    Err(err) => {
-                        error += 1;
+// the test overview log with --coverage
+fn run_check(exe_path: &Path, check: &Check, config: &ValidateConfig) -> Result<Option<String>> {
+    // testlib reads neither the arguments nor an overview log option in checker mode
+    if let Some((output, answer)) = &check.output {
+        validate_output(exe_path, &check.input, output, answer, config.timeout)?;
+        return Ok(None);
+    }
+    let mut args = config.args.for_file(&check.key);
+    let log = if config.coverage {
+        let log = tempfile::NamedTempFile::new()?;
+        args.push("--testOverviewLogFileName".to_string());
+        args.push(log.path().display().to_string());
+        Some(log)
+    } else {
+        None
+    };
+    validate_data(exe_path, &args, &check.input, config.timeout)?;
+    match log {
+        Some(log) => Ok(Some(std::fs::read_to_string(log.path())?)),
+        None => Ok(None),
+    }
+}
 
-                        let relative = path.strip_prefix(dir).unwrap_or(path);
-                        eprintln!("{} {}", "[Error]".red(), err);
-                        eprintln!("\t=> {}", relative.display());
-                    }
+/// Prints every file that fails and the totals, then fails itself if any file did or timed out.
+pub fn validate<P: AsRef<Path>>(
+    exe_path: P,
+    paths: Vec<&str>,
+    config: &ValidateConfig,
+) -> Result<()> {
+    let exe_path = exe_path.as_ref();
+    println!("{}", "Validating ..".green());
+
+    let mut good = 0;
+    let mut error = 0;
+
+    let (checks, missing) = collect_checks(&paths, config)?;
+    for (path, message) in &missing {
+        error += 1;
+        eprintln!("{} {}", "[Error]".red(), message);
+        eprintln!("\t=> {}", path.display());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.jobs)
+        .build()
+        .expect("cannot start worker threads");
+    let results: Vec<Result<Option<String>>> = pool.install(|| {
+        checks
+            .par_iter()
+            .map(|check| run_check(exe_path, check, config))
+            .collect()
+    });
+
+    let mut timed_out = 0;
+    let mut coverage = Coverage::default();
+    for (check, result) in checks.iter().zip(results) {
+        match result {
+            Ok(log) => {
+                good += 1;
+                if let Some(log) = log {
+                    coverage.add(&check.shown, &log);
                 }
             }
+            Err(Error(ErrorKind::TimedOut(seconds), _)) => {
+                timed_out += 1;
+                eprintln!("{} timed out after {}s", "[Timeout]".yellow(), seconds);
+                eprintln!("\t=> {}", check.shown.display());
+            }
+            Err(err) => {
+                error += 1;
+                eprintln!("{} {}", "[Error]".red(), err);
+                eprintln!("\t=> {}", check.shown.display());
+            }
+        }
+    }
+    if config.coverage {
+        if coverage.is_empty() {
+            println!("No coverage: the validator reads no named variables or features");
+        } else {
+            coverage.print();
         }
     }
     println!("   {}: {}", "Good".green(), good);
     println!("  {}: {}", "Error".red(), error);
+    println!("{}: {}", "Timeout".yellow(), timed_out);
+    if config.coverage {
+        println!(" {}: {}", "Missed".yellow(), coverage.misses());
+    }
+    if error + timed_out > 0 {
+        return Err(format!(
+            "{} of {} files failed",
+            error + timed_out,
+            error + timed_out + good
+        )
+        .into());
+    }
     Ok(())
 }
 
+// a checker still running after this is a judge error
+const CHECKER_TIME: Duration = Duration::from_secs(10);
+
+/// Runs `checker input output answer` and maps the testlib exit codes: 0 ok, 1 wrong answer,
+/// 2 presentation error, 3 fail and 7 points (`points 0.5 ..`). Also returns its first line.
+pub fn run_checker(
+    checker: &Path,
+    input: &Path,
+    output: &Path,
+    answer: &Path,
+) -> Result<(JudgeResult, Option<String>)> {
+    let (status, stderr) = run_with_stderr(
+        Command::new(checker)
+            .arg(input)
+            .arg(output)
+            .arg(answer)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null()),
+        Some(CHECKER_TIME),
+    )?;
+    let message = String::from_utf8_lossy(&stderr);
+    let comment = message
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(cut_line);
+    let points = comment
+        .as_deref()
+        .and_then(|c| c.strip_prefix("points "))
+        .and_then(|c| c.split_whitespace().next())
+        .and_then(|p| p.parse::<f64>().ok());
+    let result = match (status.code(), points) {
+        (Some(0), _) => JudgeResult::Correct,
+        (Some(1), _) => JudgeResult::WrongAnswer,
+        (Some(2), _) => JudgeResult::PresentationError,
+        (Some(7), Some(points)) => JudgeResult::PartialPoints(points),
+        (Some(3), _) => JudgeResult::JudgeFailure,
+        _ => {
+            let comment = match comment {
+                Some(c) => format!("checker {}: {}", describe_status(status), c),
+                None => format!("checker {}", describe_status(status)),
+            };
+            return Ok((JudgeResult::JudgeFailure, Some(comment)));
+        }
+    };
+    Ok((result, comment))
+}
+
 pub fn eval_case<P1: AsRef<Path>, P2: AsRef<Path>>(
     input: P1,
     answer: P2,
     config: &EvalConfig,
 ) -> Result<JudgeResult> {
+    judge_case(input, answer, config).map(|(result, _)| result)
+}
+
+/// `eval_case` with the checker's comment.
+pub fn judge_case<P1: AsRef<Path>, P2: AsRef<Path>>(
+    input: P1,
+    answer_path: P2,
+    config: &EvalConfig,
+) -> Result<(JudgeResult, Option<String>)> {
     let input = input.as_ref();
     let f: File = File::open(&input).chain_err(|| "fail to open input file")?;
 
-    let answer = File::open(&answer).chain_err(|| "fail to open answer file")?;
+    let answer = File::open(&answer_path).chain_err(|| "fail to open answer file")?;
     let child_limit = config.limit.clone();
 
+    // the checker reads the output from a file
+    let output = match config.checker {
+        Some(_) => Some(tempfile::NamedTempFile::new()?),
+        None => None,
+    };
+    let stdout = match &output {
+        Some(output) => Stdio::from(output.reopen()?),
+        None => Stdio::piped(),
+    };
+
     let mut child = Command::new(config.cmd)
         .args(&config.args)
         .stdin(f)
-        .stdout(Stdio::piped())
+        .stdout(stdout)
         .before_exec(move || {
             sandbox::set_limits(&child_limit).unwrap();
             Ok(())
@@ -129,41 +611,38 @@ pub fn eval_case<P1: AsRef<Path>, P2: AsRef<Path>>(
         .chain_err(|| "fail to spawn solution process")?;
 
     let time = Duration::from_secs_f64(config.limit.time.unwrap_or(1.0) as f64);
-    let success = match child
-        .wait_timeout(time)
-        .chain_err(|| "fail to wait for solution process")?
-    {
-        Some(status) => status.success(),
-        None => {
-            child.kill()?;
-            child.wait()?;
-            return Ok(JudgeResult::TimeOver);
-        }
-    };
-
-    if success {
-        // AC / WA
-        let judge: bool = {
-            let stdout = child.stdout.take();
-            let user_it = stdout.unwrap().bytes().map(|e| e.unwrap());
-            let ans_it = answer.bytes().map(|e| e.unwrap()); // LF only
-
-            if cfg!(windows) {
-                let user_it = user_it.filter(|b| *b != b'\r'); // LF? CRLF? ignore CR
-                let ans_it = ans_it.filter(|b| *b != b'\r'); // LF? CRLF? ignore CR
-                user_it.eq(ans_it)
-            } else {
-                user_it.eq(ans_it)
-            }
+    let success =
+        match wait_or_kill(&mut child, time).chain_err(|| "fail to wait for solution process")? {
+            Some(status) => status.success(),
+            None => return Ok((JudgeResult::TimeOver, None)),
         };
-        if judge {
-            Ok(JudgeResult::Correct)
+
+    if !success {
+        // runtime error
+        return Ok((JudgeResult::RuntimeError, None));
+    }
+    if let (Some(checker), Some(output)) = (config.checker, &output) {
+        return run_checker(checker, input, output.path(), answer_path.as_ref());
+    }
+
+    // AC / WA
+    let judge: bool = {
+        let stdout = child.stdout.take();
+        let user_it = stdout.unwrap().bytes().map(|e| e.unwrap());
+        let ans_it = answer.bytes().map(|e| e.unwrap()); // LF only
+
+        if cfg!(windows) {
+            let user_it = user_it.filter(|b| *b != b'\r'); // LF? CRLF? ignore CR
+            let ans_it = ans_it.filter(|b| *b != b'\r'); // LF? CRLF? ignore CR
+            user_it.eq(ans_it)
         } else {
-            Ok(JudgeResult::WrongAnswer)
+            user_it.eq(ans_it)
         }
+    };
+    if judge {
+        Ok((JudgeResult::Correct, None))
     } else {
-        // runtime error
-        Ok(JudgeResult::RuntimeError)
+        Ok((JudgeResult::WrongAnswer, None))
     }
 }
 
@@ -174,100 +653,65 @@ pub fn eval<P1: AsRef<Path>, P2: AsRef<Path>>(
     out_filter: &str,
     limit: &Limitation,
     ignore_cr: bool,
+    checker: Option<&Path>,
 ) -> Result<()> {
     println!("{}\n", "Evaluating ..".green());
 
-    let config = {
-        let solution = solution.as_ref();
-
-        if !solution.is_file() {
-            return Err("solution file does not exist")?;
-        }
-
-        let is_java = {
-            let ext: Option<&str> = solution.extension().and_then(|s| s.to_str());
-            ext.is_some() && ext.unwrap() == "class"
-        };
-
-        let (cmd, args) = {
-            if is_java {
-                let cp = solution
-                    .parent()
-                    .and_then(|p| p.to_str())
-                    .unwrap_or(".")
-                    .to_string();
-                let stem = solution.file_stem().unwrap().to_str().unwrap().to_string();
-                ("java", ["-cp".to_string(), cp, stem].to_vec())
-            } else {
-                (solution.to_str().unwrap(), Vec::new())
-            }
-        };
-        // let ignore_cr = ignore_cr.unwrap_or(cfg!(windows));
-        EvalConfig {
-            cmd,
-            args,
-            limit: &limit,
-            ignore_cr,
-        }
-    };
+    let mut config = EvalConfig::for_solution(solution.as_ref(), limit, ignore_cr)?;
+    config.checker = checker;
 
     let data_dir = data_dir.as_ref();
     let mut correct = 0;
     let mut incorrect = 0;
+    let mut partial = 0;
+    let mut points = 0.0;
     let mut judge_error = 0;
 
-    let inputs: Vec<_> = {
-        let mut v: Vec<_> = WalkDir::new(data_dir)
-            .into_iter()
-            .map(|e| e.expect("fail to list dir").into_path())
-            .filter(|p| {
-                p.file_name()
-                    .expect(&format!("no file name: {:?}", p.canonicalize()))
-                    .to_str()
-                    .expect("non-utf8")
-                    .contains(in_filter)
-            })
-            .collect();
-        v.sort();
-        v
-    };
-    let outputs: Vec<_> = {
-        let mut v: Vec<_> = WalkDir::new(data_dir)
-            .into_iter()
-            .map(|e| e.expect("fail to list dir").into_path())
-            .filter(|p| {
-                p.file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .contains(out_filter)
-            })
-            .collect();
-        v.sort();
-        v
-    };
-
-    assert!(
-        inputs.len() == outputs.len(),
-        "{} inputs != {} outputs",
-        inputs.len(),
-        outputs.len()
-    );
+    let pairing = audit::pair(data_dir, in_filter, out_filter).map_err(|e| e.to_string())?;
+    let unpaired: Vec<String> = pairing
+        .orphan_inputs
+        .iter()
+        .map(|p| format!("\n\t{} has no answer", p.display()))
+        .chain(
+            pairing
+                .orphan_answers
+                .iter()
+                .map(|p| format!("\n\t{} has no input", p.display())),
+        )
+        .collect();
+    if !unpaired.is_empty() {
+        return Err(format!("tests do not pair up:{}", unpaired.concat()).into());
+    }
 
-    for (input, output) in inputs.iter().zip(outputs.iter()) {
+    for (input, output) in &pairing.pairs {
         let relative = input.strip_prefix(data_dir).unwrap_or(input);
-        match eval_case(input, output, &config) {
-            Ok(JudgeResult::Correct) => {
-                correct += 1;
-                println!("{:>15} {}", "[Correct]".green(), relative.display());
-            }
-            Ok(reason) => {
-                incorrect += 1;
-                println!(
-                    "{:>15} {}",
-                    format!("[{:?}]", reason).red(),
-                    relative.display()
-                );
+        match judge_case(input, output, &config) {
+            Ok((result, comment)) => {
+                let tag = match result {
+                    JudgeResult::Correct => {
+                        correct += 1;
+                        "[Correct]".green()
+                    }
+                    JudgeResult::PartialPoints(p) => {
+                        partial += 1;
+                        points += p;
+                        format!("[Points {}]", p).yellow()
+                    }
+                    JudgeResult::JudgeFailure => {
+                        judge_error += 1;
+                        "[Judge Failure]".purple()
+                    }
+                    reason => {
+                        incorrect += 1;
+                        format!("[{:?}]", reason).red()
+                    }
+                };
+                match comment {
+                    Some(comment) => {
+                        println!("{:>15} {}  {}", tag, relative.display(), comment.dimmed())
+                    }
+                    None => println!("{:>15} {}", tag, relative.display()),
+                }
             }
             Err(err) => {
                 judge_error += 1;
@@ -279,6 +723,125 @@ pub fn eval<P1: AsRef<Path>, P2: AsRef<Path>>(
     println!();
     println!("       {}: {}", "Good".green(), correct);
     println!("  {}: {}", "Incorrect".red(), incorrect);
+    if partial > 0 {
+        println!(
+            "    {}: {} ({} points)",
+            "Partial".yellow(),
+            partial,
+            points
+        );
+    }
     println!("{}: {}", "Judge Error".purple(), judge_error);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    // runs `script` as the checker of a small test
+    fn judge(dir: &Path, script: &str) -> (JudgeResult, Option<String>) {
+        let checker = dir.join("checker.sh");
+        std::fs::write(&checker, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&checker, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (input, output, answer) = (dir.join("1.in"), dir.join("1.res"), dir.join("1.out"));
+        std::fs::write(&input, "1 2\n").unwrap();
+        std::fs::write(&output, "3\n").unwrap();
+        std::fs::write(&answer, "3\n").unwrap();
+        run_checker(&checker, &input, &output, &answer).unwrap()
+    }
+
+    fn exits(code: i32, message: &str) -> (JudgeResult, Option<String>) {
+        let dir = tempfile::tempdir().unwrap();
+        judge(
+            dir.path(),
+            &format!("echo '{}' >&2\nexit {}", message, code),
+        )
+    }
+
+    #[test]
+    fn testlib_exit_codes() {
+        assert_eq!(
+            exits(0, "ok 1 number"),
+            (JudgeResult::Correct, Some("ok 1 number".into()))
+        );
+        assert_eq!(
+            exits(1, "wrong answer 1st numbers differ").0,
+            JudgeResult::WrongAnswer
+        );
+        assert_eq!(
+            exits(2, "wrong output format").0,
+            JudgeResult::PresentationError
+        );
+        assert_eq!(
+            exits(3, "FAIL answer is wrong").0,
+            JudgeResult::JudgeFailure
+        );
+        assert_eq!(
+            exits(7, "points 0.25 half done"),
+            (
+                JudgeResult::PartialPoints(0.25),
+                Some("points 0.25 half done".into())
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_exit_is_a_judge_failure() {
+        let (result, comment) = exits(5, "strange");
+        assert_eq!(result, JudgeResult::JudgeFailure);
+        assert_eq!(comment.as_deref(), Some("checker exit code 5: strange"));
+        // points exit code without points
+        let (result, comment) = exits(7, "no points here");
+        assert_eq!(result, JudgeResult::JudgeFailure);
+        assert_eq!(
+            comment.as_deref(),
+            Some("checker exit code 7: no points here")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let (result, comment) = judge(dir.path(), "kill -KILL $$");
+        assert_eq!(result, JudgeResult::JudgeFailure);
+        assert_eq!(comment.as_deref(), Some("checker killed by SIGKILL"));
+    }
+
+    #[test]
+    fn input_output_answer_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = r#"[ "$(basename "$1") $(basename "$2") $(basename "$3")" = "1.in 1.res 1.out" ] || exit 3"#;
+        assert_eq!(judge(dir.path(), script).0, JudgeResult::Correct);
+    }
+
+    #[test]
+    fn checker_mode_takes_no_validator_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let (data, out) = (dir.path().join("data"), dir.path().join("out"));
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(data.join("1.in"), "1 2\n").unwrap();
+        std::fs::write(data.join("1.out"), "3\n").unwrap();
+        std::fs::write(out.join("1.out"), "3\n").unwrap();
+        // like testlib's registerTestlibCmd
+        let checker = dir.path().join("checker.sh");
+        std::fs::write(&checker, "#!/bin/sh\n[ $# -eq 3 ] || exit 3\n").unwrap();
+        std::fs::set_permissions(&checker, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = ValidateConfig {
+            filter: regex::Regex::new(".*").unwrap(),
+            args: ValidatorArgs {
+                common: vec!["--group".into(), "1".into()],
+                mapped: Vec::new(),
+            },
+            outputs: Some(Outputs {
+                dir: out,
+                input: ".in".into(),
+                answer: ".out".into(),
+            }),
+            coverage: false,
+            jobs: 1,
+            timeout: None,
+        };
+        validate(&checker, vec![data.to_str().unwrap()], &config).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+// samples in a statement against the sample tests of a problem
+
+use crate::runner;
+use colored::*;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Class, Name};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct Sample {
+	pub input: String,
+	pub output: String,
+}
+
+// the text of a <pre>, with <br> and the line <div>s of newer Codeforces statements as newlines
+fn pre_text(node: Node) -> String {
+	let mut s = String::new();
+	for d in node.descendants() {
+		if let Some(text) = d.as_text() {
+			s.push_str(text);
+		} else if matches!(d.name(), Some("br") | Some("div")) {
+			s.push('\n');
+		}
+	}
+	s
+}
+
+// the <pre> in each element of `class`, or the element itself
+fn blocks(doc: &Document, class: &str) -> Vec<String> {
+	doc.find(Class(class))
+		.map(|node| match node.find(Name("pre")).next() {
+			Some(pre) => pre_text(pre),
+			None => pre_text(node),
+		})
+		.collect()
+}
+
+/// Codeforces and Polygon statements: `input` and `output` classes, each with a `<pre>`.
+pub fn from_html(html: &str) -> Vec<Sample> {
+	let doc = Document::from(html);
+	blocks(&doc, "input")
+		.into_iter()
+		.zip(blocks(&doc, "output"))
+		.map(|(input, output)| Sample { input, output })
+		.collect()
+}
+
+/// Fenced code blocks, told apart by the last line before them ("### Sample Input 1",
+/// "예제 출력 1"), or else taken as input and output in turn.
+pub fn from_markdown(text: &str) -> Vec<Sample> {
+	let mut inputs = Vec::new();
+	let mut outputs = Vec::new();
+	let mut label = String::new();
+	let mut block: Option<String> = None;
+	for line in text.lines() {
+		let fence = line.trim_start().starts_with("```");
+		match &mut block {
+			Some(b) if fence => {
+				let label = label.to_lowercase();
+				let is_input = if label.contains("input") || label.contains("입력") {
+					true
+				} else if label.contains("output") || label.contains("출력") {
+					false
+				} else {
+					inputs.len() == outputs.len()
+				};
+				if is_input {
+					inputs.push(std::mem::take(b));
+				} else {
+					outputs.push(std::mem::take(b));
+				}
+				block = None;
+			}
+			Some(b) => {
+				b.push_str(line);
+				b.push('\n');
+			}
+			None if fence => block = Some(String::new()),
+			None if !line.trim().is_empty() => label = line.to_string(),
+			None => {}
+		}
+	}
+	inputs
+		.into_iter()
+		.zip(outputs)
+		.map(|(input, output)| Sample { input, output })
+		.collect()
+}
+
+// the last number in the file name, samples are numbered 1, 2, .., 10
+fn number(path: &Path) -> Option<u64> {
+	let name = path.file_name()?.to_str()?;
+	let digits: String = name
+		.chars()
+		.rev()
+		.skip_while(|c| !c.is_ascii_digit())
+		.take_while(|c| c.is_ascii_digit())
+		.collect();
+	digits.chars().rev().collect::<String>().parse().ok()
+}
+
+/// The sample tests in `dir`: files named with `input` and `output` (`input1.txt`, `output1.txt`),
+/// directly in `dir`, paired like `tests audit`, in the order of their numbers.
+pub fn sample_files(
+	dir: &Path,
+	input: &str,
+	output: &str,
+) -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn Error>> {
+	let mut pairs = crate::audit::pair(dir, input, output)?.pairs;
+	// not those of another problem below
+	pairs.retain(|(input, _)| input.parent() == Some(dir));
+	pairs.sort_by_key(|(input, _)| number(input));
+	Ok(pairs)
+}
+
+// where two texts differ, ignoring whitespace
+fn difference(expected: &str, found: &str) -> Option<String> {
+	let mut a = expected.split_whitespace();
+	let mut b = found.split_whitespace();
+	let mut i = 1;
+	loop {
+		match (a.next(), b.next()) {
+			(None, None) => return None,
+			(Some(x), Some(y)) if x == y => {}
+			(x, y) => {
+				let show =
+					|t: Option<&str>| t.map_or("nothing".to_string(), |t| format!("{:?}", t));
+				return Some(format!(
+					"token {}: {} in the statement, {} in the file",
+					i,
+					show(x),
+					show(y)
+				));
+			}
+		}
+		i += 1;
+	}
+}
+
+/// Prints every sample that differs from its file (or fails `validator`) and returns how many.
+pub fn check(
+	statement: &Path,
+	files: &[(PathBuf, PathBuf)],
+	validator: Option<&Path>,
+) -> Result<usize, Box<dyn Error>> {
+	let text = std::fs::read_to_string(statement)?;
+	let is_markdown = statement
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+	let samples = if is_markdown {
+		from_markdown(&text)
+	} else {
+		from_html(&text)
+	};
+	println!(
+		"Samples: {} in {}, {} sample tests",
+		samples.len(),
+		statement.display(),
+		files.len()
+	);
+
+	let mut good = 0;
+	let mut error = 0;
+	let mut report = |message: &str, path: &Path| {
+		error += 1;
+		eprintln!("{} {}", "[Error]".red(), message);
+		eprintln!("\t=> {}", path.display());
+	};
+	if samples.len() != files.len() {
+		let message = format!(
+			"{} samples in the statement, {} sample tests",
+			samples.len(),
+			files.len()
+		);
+		report(&message, statement);
+	}
+	for (i, (sample, (input, output))) in samples.iter().zip(files).enumerate() {
+		let mut ok = true;
+		for (expected, path) in [(&sample.input, input), (&sample.output, output)] {
+			let found = std::fs::read_to_string(path)?;
+			if let Some(diff) = difference(expected, &found) {
+				report(&format!("sample {} differs at {}", i + 1, diff), path);
+				ok = false;
+			}
+		}
+		if let Some(validator) = validator {
+			let timeout = Some(Duration::from_secs(10));
+			if let Err(err) = runner::validate_data(validator, &[], input, timeout) {
+				report(&format!("sample {}: {}", i + 1, err), input);
+				ok = false;
+			}
+		}
+		if ok {
+			good += 1;
+		}
+	}
+	println!("   {}: {}", "Good".green(), good);
+	println!("  {}: {}", "Error".red(), error);
+	Ok(error)
+}
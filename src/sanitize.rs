@@ -1,124 +1,1349 @@
+use crate::location::{escape, Locator};
 use colored::*;
+use rayon::prelude::*;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
 use walkdir::WalkDir;
 
-fn sanitize_file<P: AsRef<Path>>(path: P, confirmed: bool) -> Result<bool, Box<dyn Error>> {
-	// 1. already good
-	// 2. sanitized
-	// 3. error
-
-	// lowercase ext
-	// - line ending to LF (remove any CR)
-	// - make sure newline at EOF
-	// - make sure ascii
-	let path: &Path = path.as_ref();
-
-	//    if let Some(ext) = path.extension() {
-	//        if !ext.to_str()?.bytes().all(|b| b>=b'a' && b<=b'z') {
-	//
-	//            println!("  Renamed: {} => {}", path.display(),);
-	//        }
-	//    }
-
-	// 1. check
-	{
-		let f = File::open(&path)?;
-		let reader = BufReader::new(f);
-		let mut it = reader.bytes();
-		let mut cr = false;
-		let mut last: u8 = 0;
-		while let Some(b) = it.next() {
-			let b = b?;
-			// ascii range? 127=DEL
-			// non-control? 32=SPACE, 9=TAB
-			if !(b < 127 && (b >= 32 || b == 10 || b == 13)) {
-				return Err(format!("non-ascii byte: {}", b).into());
-			}
-			if b == 13 {
-				cr = true;
-			}
-			last = b;
-		}
-
-		if !cr && last == b'\n' {
-			return Ok(false); // already good
-		}
-	}
-
-	if confirmed {
-		let f = File::open(&path)?;
-		let reader = BufReader::new(f);
-		let mut it = reader.bytes();
-
-		let tmp_path = &path.with_extension("tmp");
-		let tmp_file = File::create(&tmp_path)?;
-		let mut bw = BufWriter::new(tmp_file);
-
-		// let last_cr = false; // last_cr == skip_lf
-		let mut last: u8 = 0;
-		while let Some(b) = it.next() {
-			let b = b?;
-			// it == CR
-			if last == b'\r' && b == b'\n' {
-				// pass
-			} else if b == b'\r' {
-				bw.write(&[b'\n'])?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+	// printable ascii, \t, \r, \n only
+	Ascii,
+	// UTF-8 byte order mark at the start
+	Bom,
+	// \r\n line endings
+	Crlf,
+	// lone \r between tokens, or no \n at EOF
+	Newline,
+	// spaces or tabs at the end of a line
+	TrailingSpace,
+	Tab,
+	// two or more spaces between tokens
+	DoubleSpace,
+	// blank lines at EOF
+	TrailingBlankLines,
+	// nothing but whitespace
+	Empty,
+	// valid UTF-8, the alternative to `Ascii`
+	Utf8,
+	// Unicode NFC
+	Nfc,
+	// NBSP, full-width digits, zero-width spaces, ..
+	Confusable,
+	// lowercase file extension, a rename instead of a rewrite
+	Extension,
+}
+
+pub const RULES: &[Rule] = &[
+	Rule::Ascii,
+	Rule::Bom,
+	Rule::Crlf,
+	Rule::Newline,
+	Rule::TrailingSpace,
+	Rule::Tab,
+	Rule::DoubleSpace,
+	Rule::TrailingBlankLines,
+	Rule::Empty,
+	Rule::Utf8,
+	Rule::Nfc,
+	Rule::Confusable,
+	Rule::Extension,
+];
+
+pub const RULE_NAMES: &[&str] = &[
+	"ascii",
+	"bom",
+	"crlf",
+	"newline",
+	"trailing-space",
+	"tab",
+	"double-space",
+	"trailing-blank-lines",
+	"empty",
+	"utf8",
+	"nfc",
+	"confusable",
+	"extension",
+];
+
+impl Rule {
+	pub fn name(self) -> &'static str {
+		RULE_NAMES[self as usize]
+	}
+
+	// there is no sensible rewrite for these
+	pub fn can_fix(self) -> bool {
+		!matches!(self, Rule::Ascii | Rule::Empty | Rule::Utf8)
+	}
+}
+
+impl FromStr for Rule {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		RULE_NAMES
+			.iter()
+			.position(|&name| name == s)
+			.map(|i| RULES[i])
+			.ok_or_else(|| format!("unknown sanitize rule: {}", s))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+	Off,
+	// report, the file is an error
+	Check,
+	// rewrite under --confirmed
+	Fix,
+}
+
+impl Mode {
+	pub fn name(self) -> &'static str {
+		match self {
+			Mode::Off => "off",
+			Mode::Check => "check",
+			Mode::Fix => "fix",
+		}
+	}
+}
+
+impl FromStr for Mode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"off" => Ok(Mode::Off),
+			"check" => Ok(Mode::Check),
+			"fix" => Ok(Mode::Fix),
+			_ => Err(format!("unknown rule mode: {} (off, check or fix)", s)),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+	modes: [Mode; RULES.len()],
+}
+
+impl Default for RuleSet {
+	// what sanitize always did: ascii only, LF line endings, newline at EOF.
+	// upper-case extensions are only reported, renaming them needs `--fix extension`
+	fn default() -> Self {
+		let mut rules = RuleSet::none();
+		rules.modes[Rule::Ascii as usize] = Mode::Check;
+		rules.modes[Rule::Bom as usize] = Mode::Check;
+		rules.modes[Rule::Tab as usize] = Mode::Check;
+		rules.modes[Rule::Crlf as usize] = Mode::Fix;
+		rules.modes[Rule::Newline as usize] = Mode::Fix;
+		rules.modes[Rule::Extension as usize] = Mode::Check;
+		rules
+	}
+}
+
+impl RuleSet {
+	pub fn none() -> Self {
+		RuleSet {
+			modes: [Mode::Off; RULES.len()],
+		}
+	}
+
+	/// Valid UTF-8 instead of ASCII, with confusable characters checked.
+	pub fn utf8(&mut self) {
+		self.modes[Rule::Ascii as usize] = Mode::Off;
+		self.modes[Rule::Utf8 as usize] = Mode::Check;
+		if self.mode(Rule::Confusable) == Mode::Off {
+			self.modes[Rule::Confusable as usize] = Mode::Check;
+		}
+	}
+
+	/// Reads `rule = off | check | fix` lines on top of the defaults. `#` starts a comment.
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+		let path = path.as_ref();
+		let text = std::fs::read_to_string(path)?;
+		let mut rules = RuleSet::default();
+		for (i, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let (rule, mode) = line
+				.split_once('=')
+				.ok_or_else(|| format!("{}:{}: expected `rule = mode`", path.display(), i + 1))?;
+			let rule: Rule = rule.trim().parse()?;
+			let mode: Mode = mode.trim().parse()?;
+			rules.set(rule, mode)?;
+		}
+		Ok(rules)
+	}
+
+	pub fn set(&mut self, rule: Rule, mode: Mode) -> Result<(), Box<dyn Error>> {
+		if mode == Mode::Fix && !rule.can_fix() {
+			return Err(format!("{} can only be checked", rule.name()).into());
+		}
+		self.modes[rule as usize] = mode;
+		Ok(())
+	}
+
+	pub fn mode(&self, rule: Rule) -> Mode {
+		self.modes[rule as usize]
+	}
+
+	fn fixes(&self, rule: Rule) -> bool {
+		self.mode(rule) == Mode::Fix
+	}
+}
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// locations listed for each rule, the rest are only counted
+const MAX_SHOWN: usize = 10;
+
+// changed lines in the preview without --confirmed
+const MAX_DIFF: usize = 3;
+
+// a line and its terminator: \n, \r\n, \r or empty at EOF
+struct Line<'a> {
+	start: usize,
+	text: &'a [u8],
+	term: &'a [u8],
+}
+
+fn lines(data: &[u8]) -> Vec<Line<'_>> {
+	let mut v = Vec::new();
+	let mut start = 0;
+	let mut i = 0;
+	let mut push = |start: usize, end: usize, term_end: usize| {
+		v.push(Line {
+			start,
+			text: &data[start..end],
+			term: &data[end..term_end],
+		})
+	};
+	while i < data.len() {
+		match data[i] {
+			b'\n' => {
+				push(start, i, i + 1);
+				start = i + 1;
+			}
+			b'\r' if data.get(i + 1) == Some(&b'\n') => {
+				push(start, i, i + 2);
+				i += 1;
+				start = i + 1;
+			}
+			b'\r' => {
+				push(start, i, i + 1);
+				start = i + 1;
+			}
+			_ => {}
+		}
+		i += 1;
+	}
+	if start < data.len() {
+		push(start, data.len(), data.len());
+	}
+	v
+}
+
+// the ASCII look-alike, '\0' to drop the character
+fn confusable(c: char) -> Option<char> {
+	match c {
+		// no-break, fixed-width and ideographic spaces
+		'\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => Some(' '),
+		// zero-width, and a BOM that is not at the start
+		'\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' => Some('\0'),
+		// hyphens, dashes, minus sign
+		'\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{FE63}' => Some('-'),
+		// full-width ASCII: digits, letters, punctuation
+		'\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+		_ => None,
+	}
+}
+
+// offsets of invalid UTF-8 sequences, or of confusable characters in the valid parts
+fn scan_utf8(bytes: &[u8], invalid: bool) -> Vec<usize> {
+	let mut v = Vec::new();
+	let mut offset = 0;
+	for chunk in bytes.utf8_chunks() {
+		let valid = chunk.valid();
+		if invalid {
+			if !chunk.invalid().is_empty() {
+				v.push(offset + valid.len());
+			}
+		} else {
+			v.extend(
+				valid
+					.char_indices()
+					.filter(|&(_, c)| confusable(c).is_some())
+					.map(|(i, _)| offset + i),
+			);
+		}
+		offset += valid.len() + chunk.invalid().len();
+	}
+	v
+}
+
+fn is_blank(line: &[u8]) -> bool {
+	line.iter().all(|&b| b == b' ' || b == b'\t')
+}
+
+// starts of the runs of 2+ spaces with a token on both sides
+fn double_spaces(line: &[u8]) -> Vec<usize> {
+	let mut v = Vec::new();
+	let mut run = 0;
+	let mut seen_token = false;
+	for (i, &b) in line.iter().enumerate() {
+		if b == b' ' {
+			run += 1;
+		} else {
+			if seen_token && run >= 2 {
+				v.push(i - run);
+			}
+			run = 0;
+			seen_token = true;
+		}
+	}
+	v
+}
+
+fn trailing_blank_lines(lines: &[Line]) -> usize {
+	lines.iter().rev().take_while(|l| is_blank(l.text)).count()
+}
+
+fn positions<F: Fn(u8) -> bool>(data: &[u8], pred: F) -> Vec<usize> {
+	data.iter()
+		.enumerate()
+		.filter(|&(_, &b)| pred(b))
+		.map(|(i, _)| i)
+		.collect()
+}
+
+// the text after a BOM and its lines, split once and shared by every rule
+fn split(data: &[u8]) -> (&[u8], Vec<Line<'_>>) {
+	let body = data.strip_prefix(BOM).unwrap_or(data);
+	(body, lines(body))
+}
+
+/// Byte offsets of every violation of `rule` in `data`.
+pub fn find(data: &[u8], rule: Rule) -> Vec<usize> {
+	let (body, lines) = split(data);
+	find_in(data, body, &lines, rule)
+}
+
+fn find_in(data: &[u8], body: &[u8], lines: &[Line], rule: Rule) -> Vec<usize> {
+	let skip = data.len() - body.len();
+	let end = |l: &Line| l.start + l.text.len();
+	let v = match rule {
+		Rule::Ascii => positions(body, |b| {
+			!(b < 127 && (b >= 32 || b == b'\t' || b == b'\n' || b == b'\r'))
+		}),
+		Rule::Bom => return if skip > 0 { vec![0] } else { Vec::new() },
+		Rule::Crlf => lines
+			.iter()
+			.filter(|l| l.term == b"\r\n")
+			.map(end)
+			.collect(),
+		// a missing terminator only happens on the last line
+		Rule::Newline => lines
+			.iter()
+			.filter(|l| l.term == b"\r" || l.term.is_empty())
+			.map(end)
+			.collect(),
+		Rule::TrailingSpace => lines
+			.iter()
+			.filter_map(|l| {
+				let n = l
+					.text
+					.iter()
+					.rev()
+					.take_while(|&&b| b == b' ' || b == b'\t')
+					.count();
+				if n > 0 {
+					Some(end(l) - n)
+				} else {
+					None
+				}
+			})
+			.collect(),
+		Rule::Tab => positions(body, |b| b == b'\t'),
+		Rule::DoubleSpace => lines
+			.iter()
+			.flat_map(|l| double_spaces(l.text).into_iter().map(move |i| l.start + i))
+			.collect(),
+		Rule::TrailingBlankLines => {
+			if is_blank(body) {
+				Vec::new()
 			} else {
-				bw.write(&[b])?;
+				let k = trailing_blank_lines(lines);
+				lines[lines.len() - k..].iter().map(|l| l.start).collect()
 			}
-			last = b;
 		}
-		if last != b'\r' && last != b'\n' {
-			bw.write(&[b'\n'])?;
+		Rule::Empty => {
+			if body.iter().all(|b| b.is_ascii_whitespace()) {
+				vec![0]
+			} else {
+				Vec::new()
+			}
 		}
-		std::fs::rename(tmp_path, path)?;
+		Rule::Utf8 => scan_utf8(body, true),
+		// invalid sequences are left to `Utf8`
+		Rule::Nfc => lines
+			.iter()
+			.filter(|l| {
+				std::str::from_utf8(l.text).is_ok_and(|t| !unicode_normalization::is_nfc(t))
+			})
+			.map(|l| l.start)
+			.collect(),
+		Rule::Confusable => scan_utf8(body, false),
+		// about the path, see `rename_plan`
+		Rule::Extension => Vec::new(),
+	};
+	v.into_iter().map(|i| skip + i).collect()
+}
+
+/// `data` with every `Fix` rule applied.
+pub fn fix(data: &[u8], rules: &RuleSet) -> Vec<u8> {
+	let (body, lines) = split(data);
+	fix_in(data, body, &lines, rules)
+}
+
+fn fix_in(data: &[u8], body: &[u8], lines: &[Line], rules: &RuleSet) -> Vec<u8> {
+	let mut lines = lines;
+	if rules.fixes(Rule::TrailingBlankLines) && !is_blank(body) {
+		lines = &lines[..lines.len() - trailing_blank_lines(lines)];
 	}
 
-	println!("Converted: {}", path.display());
-	Ok(true)
+	let mut out = Vec::with_capacity(data.len() + 1);
+	if body.len() < data.len() && !rules.fixes(Rule::Bom) {
+		out.extend_from_slice(BOM);
+	}
+	for &Line {
+		text: line, term, ..
+	} in lines
+	{
+		let normalized;
+		let line = match std::str::from_utf8(line) {
+			Ok(text) if rules.fixes(Rule::Confusable) || rules.fixes(Rule::Nfc) => {
+				let mut text = text.to_string();
+				if rules.fixes(Rule::Confusable) {
+					text = text
+						.chars()
+						.filter_map(|c| match confusable(c) {
+							Some('\0') => None,
+							Some(a) => Some(a),
+							None => Some(c),
+						})
+						.collect();
+				}
+				if rules.fixes(Rule::Nfc) {
+					text = text.nfc().collect();
+				}
+				normalized = text;
+				normalized.as_bytes()
+			}
+			_ => line,
+		};
+		let mut line: Vec<u8> = if rules.fixes(Rule::Tab) {
+			line.iter()
+				.map(|&b| if b == b'\t' { b' ' } else { b })
+				.collect()
+		} else {
+			line.to_vec()
+		};
+		if rules.fixes(Rule::TrailingSpace) {
+			while matches!(line.last(), Some(b' ') | Some(b'\t')) {
+				line.pop();
+			}
+		}
+		if rules.fixes(Rule::DoubleSpace) {
+			// keep the indentation
+			let indent = line.iter().take_while(|&&b| b == b' ').count();
+			let mut collapsed = line[..indent].to_vec();
+			let mut run = 0;
+			for &b in &line[indent..] {
+				if b == b' ' {
+					run += 1;
+					continue;
+				}
+				if run > 0 {
+					collapsed.push(b' ');
+					run = 0;
+				}
+				collapsed.push(b);
+			}
+			collapsed.resize(collapsed.len() + run, b' ');
+			line = collapsed;
+		}
+		out.extend_from_slice(&line);
+
+		let term: &[u8] = match term {
+			b"\r\n" if rules.fixes(Rule::Crlf) => b"\n",
+			b"\r" | b"" if rules.fixes(Rule::Newline) => b"\n",
+			_ => term,
+		};
+		out.extend_from_slice(term);
+	}
+	out
 }
 
-pub fn sanitize<P: AsRef<Path>>(path: P, exts: Vec<&str>, confirmed: bool) {
-	println!("   Exts: {:?}", exts);
+/// Violations of every rule that is not `Off`, as `(rule, offsets)`.
+pub fn violations(data: &[u8], rules: &RuleSet) -> Vec<(Rule, Vec<usize>)> {
+	let (body, lines) = split(data);
+	violations_in(data, body, &lines, rules)
+}
+
+fn violations_in(
+	data: &[u8],
+	body: &[u8],
+	lines: &[Line],
+	rules: &RuleSet,
+) -> Vec<(Rule, Vec<usize>)> {
+	RULES
+		.iter()
+		.filter(|&&rule| rules.mode(rule) != Mode::Off)
+		.map(|&rule| (rule, find_in(data, body, lines, rule)))
+		.filter(|(_, v)| !v.is_empty())
+		.collect()
+}
+
+fn summary(v: &[(Rule, Vec<usize>)]) -> String {
+	let items: Vec<String> = v
+		.iter()
+		.map(|(rule, offsets)| format!("{} {}", offsets.len(), rule.name()))
+		.collect();
+	items.join(", ")
+}
+
+// one `line:col rule "snippet"` per violation, up to `MAX_SHOWN` for each rule
+fn details(data: &[u8], v: &[(Rule, Vec<usize>)]) -> String {
+	let locator = Locator::new(data);
+	let mut s = String::new();
+	for (rule, offsets) in v {
+		for &offset in offsets.iter().take(MAX_SHOWN) {
+			let (line, column) = locator.position(offset);
+			s.push_str(&format!(
+				"\n\t{}:{} {} {}",
+				line,
+				column,
+				rule.name(),
+				locator.snippet(offset)
+			));
+		}
+		if offsets.len() > MAX_SHOWN {
+			s.push_str(&format!(
+				"\n\t.. {} more {}",
+				offsets.len() - MAX_SHOWN,
+				rule.name()
+			));
+		}
+	}
+	s
+}
+
+// "3 CRLF→LF, added final newline"
+fn fix_summary(data: &[u8], v: &[(Rule, Vec<usize>)]) -> String {
+	let mut items = Vec::new();
+	for (rule, offsets) in v {
+		let n = offsets.len();
+		match rule {
+			Rule::Bom => items.push("removed BOM".to_string()),
+			Rule::Crlf => items.push(format!("{} CRLF→LF", n)),
+			Rule::Newline => {
+				let eof = offsets.contains(&data.len()) as usize;
+				if n > eof {
+					items.push(format!("{} CR→LF", n - eof));
+				}
+				if eof > 0 {
+					items.push("added final newline".to_string());
+				}
+			}
+			Rule::TrailingSpace => items.push(format!("trailing spaces on {} lines", n)),
+			Rule::Tab => items.push(format!("{} tab→space", n)),
+			Rule::DoubleSpace => items.push(format!("collapsed {} double spaces", n)),
+			Rule::TrailingBlankLines => items.push(format!("removed {} blank lines at EOF", n)),
+			Rule::Nfc => items.push(format!("{} lines to NFC", n)),
+			Rule::Confusable => items.push(format!("{} confusable→ASCII", n)),
+			_ => items.push(format!("{} {}", n, rule.name())),
+		}
+	}
+	items.join(", ")
+}
+
+// whitespace made visible: · space, → tab, ␍ CR, ␊ LF
+fn visible(line: &Line) -> String {
+	let mut s = String::new();
+	for piece in line.text.split_inclusive(|&b| b == b' ' || b == b'\t') {
+		match piece.split_last() {
+			Some((b' ', rest)) => {
+				s.push_str(&escape(rest));
+				s.push('·');
+			}
+			Some((b'\t', rest)) => {
+				s.push_str(&escape(rest));
+				s.push('→');
+			}
+			_ => s.push_str(&escape(piece)),
+		}
+	}
+	for &b in line.term {
+		s.push(if b == b'\r' { '␍' } else { '␊' });
+	}
+	s
+}
+
+// the first `MAX_DIFF` changed lines as `-` / `+` pairs
+fn preview(data: &[u8], fixed: &[u8]) -> String {
+	let old = lines(data);
+	let new = lines(fixed);
+	let mut s = String::new();
+	let mut shown = 0;
+	let mut changed = 0;
+	for i in 0..std::cmp::max(old.len(), new.len()) {
+		let (a, b) = (old.get(i), new.get(i));
+		if a.map(|l| (l.text, l.term)) == b.map(|l| (l.text, l.term)) {
+			continue;
+		}
+		changed += 1;
+		if shown == MAX_DIFF {
+			continue;
+		}
+		shown += 1;
+		s.push_str(&format!("\n\t@@ line {}", i + 1));
+		if let Some(a) = a {
+			s.push_str(&format!("\n\t{} {}", "-".red(), visible(a).red()));
+		}
+		if let Some(b) = b {
+			s.push_str(&format!("\n\t{} {}", "+".green(), visible(b).green()));
+		}
+	}
+	if changed > shown {
+		s.push_str(&format!("\n\t.. {} more changed lines", changed - shown));
+	}
+	s
+}
+
+/// Replaces `path` with `data`: a fresh temporary file next to it with the same permissions and
+/// times, fsync, then rename. The temporary file is removed if anything fails.
+fn rewrite(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+	let meta = std::fs::metadata(path)?;
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new("."),
+	};
+	let name = path.file_name().unwrap_or_default().to_string_lossy();
+	let mut tmp = tempfile::Builder::new()
+		.prefix(&format!(".{}.", name))
+		.suffix(".tmp")
+		.tempfile_in(dir)?;
+	tmp.write_all(data)?;
+	tmp.as_file().set_permissions(meta.permissions())?;
+	let times = std::fs::FileTimes::new()
+		.set_accessed(meta.accessed()?)
+		.set_modified(meta.modified()?);
+	tmp.as_file().set_times(times)?;
+	tmp.as_file().sync_all()?;
+	tmp.persist(path)?;
+	// the rename itself
+	File::open(dir)?.sync_all()?;
+	Ok(())
+}
+
+// copy the original to `dest`, or to `dest.1`, `dest.2`, .. if an older backup is there
+fn backup(path: &Path, dest: &Path) -> Result<PathBuf, Box<dyn Error>> {
+	if let Some(parent) = dest.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+	let mut target = dest.to_path_buf();
+	let mut i = 0;
+	while target.exists() {
+		i += 1;
+		let mut name = dest.as_os_str().to_owned();
+		name.push(format!(".{}", i));
+		target = PathBuf::from(name);
+	}
+	std::fs::copy(path, &target)?;
+	Ok(target)
+}
+
+// bytes that never violate a rule on their own
+const CHUNK: usize = 1 << 20;
+
+// printable ASCII, single spaces and \n, no space before \n
+fn plain_chunk(chunk: &[u8], mut prev: u8) -> bool {
+	for &b in chunk {
+		let ok = match b {
+			b'!'..=b'~' => true,
+			b' ' => prev != b' ',
+			b'\n' => prev != b' ',
+			_ => false,
+		};
+		if !ok {
+			return false;
+		}
+		prev = b;
+	}
+	true
+}
+
+/// 64-bit FNV-1a, the same in every build, so it can be stored.
+pub fn content_hash(data: &[u8]) -> u64 {
+	fnv(0xcbf29ce484222325, data)
+}
+
+// continues the hash `h` with `data`
+fn fnv(mut h: u64, data: &[u8]) -> u64 {
+	for &b in data {
+		h ^= b as u64;
+		h = h.wrapping_mul(0x100000001b3);
+	}
+	h
+}
+
+/// `content_hash` of the file, and whether a quick scan guarantees no violation of any content
+/// rule (`false` means the rules have to look). Only one chunk is in memory at a time.
+fn scan(path: &Path) -> std::io::Result<(u64, bool)> {
+	let mut file = File::open(path)?;
+	let mut buf = vec![0; CHUNK];
+	let mut hash = content_hash(b"");
+	let mut plain = true;
+	// the last two bytes, the file starts as if after a newline
+	let mut last = [0, b'\n'];
+	let mut len = 0;
+	loop {
+		let n = match file.read(&mut buf) {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(err) => return Err(err),
+		};
+		let chunk = &buf[..n];
+		hash = fnv(hash, chunk);
+		plain = plain && plain_chunk(chunk, last[1]);
+		last = if n >= 2 {
+			[chunk[n - 2], chunk[n - 1]]
+		} else {
+			[last[1], chunk[0]]
+		};
+		len += n;
+	}
+	// exactly one newline at EOF, and not a blank "\n"
+	Ok((
+		hash,
+		plain && len >= 2 && last[1] == b'\n' && last[0] != b'\n',
+	))
+}
+
+// what happened to one file
+enum Outcome {
+	Good,
+	// what was (or would be) fixed, and the diff without --confirmed
+	Changed(String, Option<String>),
+}
+
+// the outcome and the cache entry of the file as it is left, `entry` being the one it had
+fn sanitize_data(
+	path: &Path,
+	data: &[u8],
+	entry: Entry,
+	opt: &Options,
+	backup_to: Option<&Path>,
+) -> Result<(Outcome, Option<Entry>), Box<dyn Error>> {
+	let rules = &opt.rules;
+	let (body, lines) = split(data);
+	let found = violations_in(data, body, &lines, rules);
+	let (checked, fixable): (Vec<_>, Vec<_>) = found
+		.into_iter()
+		.partition(|(rule, _)| rules.mode(*rule) == Mode::Check);
+	if !checked.is_empty() {
+		return Err(format!("{}{}", summary(&checked), details(data, &checked)).into());
+	}
+	if fixable.is_empty() {
+		return Ok((Outcome::Good, Some(entry)));
+	}
+
+	let fixed = fix_in(data, body, &lines, rules);
+	// not needed while the fixed copy is written
+	drop(lines);
+	let summary = fix_summary(data, &fixable);
+	if opt.confirmed {
+		if let Some(dest) = backup_to {
+			backup(path, dest)?;
+		}
+		rewrite(path, &fixed)?;
+		// `rewrite` keeps the mtime
+		let entry = Entry {
+			hash: content_hash(&fixed),
+			size: fixed.len() as u64,
+			..entry
+		};
+		Ok((Outcome::Changed(summary, None), Some(entry)))
+	} else {
+		let preview = preview(data, &fixed);
+		Ok((Outcome::Changed(summary, Some(preview)), None))
+	}
+}
+
+// a file that passed or was fixed,
+// unchanged as long as the size and mtime (or else the hash) are the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+	hash: u64,
+	size: u64,
+	mtime: u128,
+}
+
+// one per root, named by the hash of its absolute path
+fn cache_file(root: &Path) -> Option<PathBuf> {
+	let root = std::fs::canonicalize(root).ok()?;
+	let hash = content_hash(root.as_os_str().as_bytes());
+	Some(crate::compile::cache_dir().join(format!("sanitize-{:016x}", hash)))
+}
+
+fn mtime(meta: &std::fs::Metadata) -> u128 {
+	meta.modified()
+		.ok()
+		.and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+		.map_or(0, |d| d.as_nanos())
+}
+
+// # rules: ascii=check ..
+// hash size mtime path
+fn load_cache(path: &Path, rules: &str) -> HashMap<PathBuf, Entry> {
+	let mut cache = HashMap::new();
+	let text = match std::fs::read_to_string(path) {
+		Ok(text) => text,
+		Err(_) => return cache,
+	};
+	let mut lines = text.lines();
+	// another rule set passes other files
+	if lines.next() != Some(&format!("# rules: {}", rules)) {
+		return cache;
+	}
+	for line in lines {
+		let mut it = line.splitn(4, ' ');
+		let (hash, size, mtime, file) = match (it.next(), it.next(), it.next(), it.next()) {
+			(Some(h), Some(s), Some(m), Some(f)) => (h, s, m, f),
+			_ => continue,
+		};
+		if let (Ok(hash), Ok(size), Ok(mtime)) =
+			(u64::from_str_radix(hash, 16), size.parse(), mtime.parse())
+		{
+			cache.insert(PathBuf::from(file), Entry { hash, size, mtime });
+		}
+	}
+	cache
+}
+
+fn save_cache(path: &Path, rules: &str, cache: &HashMap<PathBuf, Entry>) -> std::io::Result<()> {
+	if let Some(dir) = path.parent() {
+		std::fs::create_dir_all(dir)?;
+	}
+	let mut files: Vec<_> = cache.iter().collect();
+	files.sort_by_key(|(file, _)| *file);
+	let mut text = format!("# rules: {}\n", rules);
+	for (file, e) in files {
+		text.push_str(&format!(
+			"{:016x} {} {} {}\n",
+			e.hash,
+			e.size,
+			e.mtime,
+			file.display()
+		));
+	}
+	std::fs::write(path, text)
+}
+
+// the outcome and, if the file passed or was fixed, its cache entry
+fn sanitize_file(
+	path: &Path,
+	cached: Option<&Entry>,
+	opt: &Options,
+	backup_to: Option<&Path>,
+) -> Result<(Outcome, Option<Entry>), Box<dyn Error>> {
+	let meta = std::fs::metadata(path)?;
+	let (size, mtime) = (meta.len(), mtime(&meta));
+	if let Some(e) = cached {
+		if e.size == size && e.mtime == mtime {
+			return Ok((Outcome::Good, Some(*e)));
+		}
+	}
+
+	let (hash, canonical) = scan(path)?;
+	let entry = Entry { hash, size, mtime };
+	// nothing to look at, or touched but the same bytes
+	if canonical || cached.is_some_and(|e| e.hash == entry.hash && e.size == entry.size) {
+		return Ok((Outcome::Good, Some(entry)));
+	}
+	// only files the quick scan cannot pass are read as a whole
+	let data = std::fs::read(path)?;
+	sanitize_data(path, &data, entry, opt, backup_to)
+}
+
+pub struct Rename {
+	pub from: PathBuf,
+	pub to: PathBuf,
+	// why `to` cannot be used
+	pub collision: Option<String>,
+}
+
+// absolute, with symlinks resolved as far as the path exists
+fn resolve(path: &Path) -> PathBuf {
+	let mut rest = Vec::new();
+	let mut p = path;
+	loop {
+		if let Ok(base) = p.canonicalize() {
+			return rest.iter().rev().fold(base, |base, name| base.join(name));
+		}
+		match (p.parent(), p.file_name()) {
+			(Some(parent), Some(name)) => {
+				rest.push(name);
+				p = if parent.as_os_str().is_empty() {
+					Path::new(".")
+				} else {
+					parent
+				};
+			}
+			_ => return path.to_path_buf(),
+		}
+	}
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+	match (std::fs::metadata(a), std::fs::metadata(b)) {
+		(Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+		_ => false,
+	}
+}
+
+/// Renames to a lowercase extension for every file in `files` that needs one.
+///
+/// A rename collides when the target is another existing file (`1.in` next to `1.IN`),
+/// or when two files would get the same name (`1.In` and `1.IN`).
+pub fn rename_plan(files: &[PathBuf]) -> Vec<Rename> {
+	let mut plan: Vec<Rename> = files
+		.iter()
+		.filter_map(|from| {
+			let ext = from.extension()?.to_str()?;
+			let lower = ext.to_lowercase();
+			if ext == lower {
+				return None;
+			}
+			let to = from.with_extension(lower);
+			// a case-insensitive file system finds the file itself
+			let collision = if to.exists() && !same_file(from, &to) {
+				Some(format!("{} exists", to.display()))
+			} else {
+				None
+			};
+			Some(Rename {
+				from: from.clone(),
+				to,
+				collision,
+			})
+		})
+		.collect();
+
+	let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+	for r in &plan {
+		*targets.entry(r.to.clone()).or_default() += 1;
+	}
+	for r in &mut plan {
+		if r.collision.is_none() && targets[&r.to] > 1 {
+			r.collision = Some(format!(
+				"{} files would be {}",
+				targets[&r.to],
+				r.to.display()
+			));
+		}
+	}
+	plan
+}
+
+pub struct Options<'a> {
+	pub rules: RuleSet,
+	pub confirmed: bool,
+	// originals are copied here before a rewrite
+	pub backup_dir: Option<&'a Path>,
+	// 0: one per CPU
+	pub jobs: usize,
+	// skip files that passed last time, see `cache_file`
+	pub cache: bool,
+	// one JSON object on stdout instead of the text report
+	pub json: bool,
+}
+
+// every file that changed or failed, printed as it is found or kept for the JSON report
+struct Report {
+	json: bool,
+	files: Vec<serde_json::Value>,
+	// a file is an error once, however many problems it has
+	failed: HashSet<PathBuf>,
+}
+
+impl Report {
+	fn error(&mut self, path: &Path, message: &str) {
+		self.failed.insert(path.to_path_buf());
+		if self.json {
+			self.files.push(json!({
+				"path": path.display().to_string(),
+				"status": "error",
+				"message": message,
+			}));
+		} else {
+			eprintln!("[Error] {}", message);
+			eprintln!("\t=> {}", path.display());
+		}
+	}
+
+	fn changed(&mut self, path: &Path, summary: &str, preview: Option<&str>) {
+		if self.json {
+			self.files.push(json!({
+				"path": path.display().to_string(),
+				"status": "changed",
+				"message": summary,
+			}));
+		} else {
+			println!(
+				"Converted: {} ({}){}",
+				path.display(),
+				summary,
+				preview.unwrap_or("")
+			);
+		}
+	}
+
+	fn renamed(&mut self, from: &Path, to: &Path) {
+		if self.json {
+			self.files.push(json!({
+				"path": from.display().to_string(),
+				"status": "renamed",
+				"to": to.display().to_string(),
+			}));
+		}
+	}
+}
+
+// path under the root, or the file name when the root is the file itself
+fn relative<'a>(root: &Path, path: &'a Path) -> &'a Path {
+	match path.strip_prefix(root) {
+		Ok(relative) if !relative.as_os_str().is_empty() => relative,
+		_ => path.file_name().map_or(path, Path::new),
+	}
+}
+
+/// Returns whether the files are clean: nothing failed, and nothing is left to fix without --confirmed.
+pub fn sanitize<P: AsRef<Path>>(path: P, exts: Vec<&str>, opt: &Options) -> bool {
+	let root = path.as_ref();
+	let rules = &opt.rules;
+	let (confirmed, backup_dir) = (opt.confirmed, opt.backup_dir);
+	if !opt.json {
+		println!("   Exts: {:?}", exts);
+	}
+	let active: Vec<String> = RULES
+		.iter()
+		.filter(|&&rule| rules.mode(rule) != Mode::Off)
+		.map(|&rule| format!("{}={}", rule.name(), rules.mode(rule).name()))
+		.collect();
+	let active = active.join(" ");
+	if !opt.json {
+		println!("  Rules: {}", active);
+	}
 
 	let mut good = 0;
 	let mut changed = 0;
-	let mut error = 0;
+	// entries the walk could not read
+	let mut unreadable = 0;
+	let mut report = Report {
+		json: opt.json,
+		files: Vec::new(),
+		failed: HashSet::new(),
+	};
 
-	for entry in WalkDir::new(path) {
+	let mut files: Vec<PathBuf> = Vec::new();
+	// the next run would check (and back up) the copies
+	let walk = match backup_dir {
+		Some(dir) if resolve(dir).starts_with(resolve(root)) => {
+			let message = format!("the backup directory must be outside {}", root.display());
+			report.error(dir, &message);
+			None
+		}
+		_ => Some(WalkDir::new(root)),
+	};
+	for entry in walk.into_iter().flatten() {
 		match entry {
 			Ok(e) => {
-				let path = e.path();
-				if let Some(ext) = path.extension() {
-					if exts.contains(&ext.to_str().unwrap()) {
-						match sanitize_file(path, confirmed) {
-							Ok(true) => {
-								changed += 1;
-							}
-							Ok(false) => {
-								good += 1;
-							}
-							Err(err) => {
-								eprintln!("[Error] {}", err);
-								eprintln!("\t=> {}", path.display());
-							}
-						}
+				// symlinks are skipped, a rewrite would replace them with a regular file
+				if !e.file_type().is_file() {
+					continue;
+				}
+				// `1.IN` is a test too, the extension rule takes care of the case
+				let ext = e.path().extension().and_then(|ext| ext.to_str());
+				if let Some(ext) = ext {
+					if exts.iter().any(|x| x.eq_ignore_ascii_case(ext)) {
+						files.push(e.into_path());
 					}
 				}
 			}
+			Err(err) => match err.path() {
+				Some(path) => report.error(path, &err.to_string()),
+				None => {
+					unreadable += 1;
+					if !opt.json {
+						println!("[File Error] {:?}", err);
+					}
+				}
+			},
+		}
+	}
+
+	files.sort();
+
+	// renamed under --confirmed, or to be renamed
+	let mut renamed: HashSet<PathBuf> = HashSet::new();
+	let mode = rules.mode(Rule::Extension);
+	let plan = if mode == Mode::Off {
+		Vec::new()
+	} else {
+		rename_plan(&files)
+	};
+	if !plan.is_empty() && !opt.json {
+		println!("Rename plan:");
+		for r in &plan {
+			match &r.collision {
+				Some(reason) => println!(
+					"  {} => {} {}",
+					r.from.display(),
+					r.to.display(),
+					format!("[collision: {}]", reason).red()
+				),
+				None => println!("  {} => {}", r.from.display(), r.to.display()),
+			}
+		}
+	}
+	for r in &plan {
+		if let Some(reason) = &r.collision {
+			report.error(&r.from, &format!("cannot rename: {}", reason));
+			continue;
+		}
+		if mode == Mode::Check {
+			report.error(&r.from, "extension is not lowercase");
+			continue;
+		}
+		if confirmed {
+			if let Err(err) = std::fs::rename(&r.from, &r.to) {
+				report.error(&r.from, &err.to_string());
+				continue;
+			}
+			let file = files.iter_mut().find(|f| **f == r.from).unwrap();
+			*file = r.to.clone();
+		}
+		report.renamed(&r.from, &r.to);
+		renamed.insert(r.from.clone());
+		renamed.insert(r.to.clone());
+	}
+
+	let cache_path = if root.is_dir() && opt.cache {
+		cache_file(root)
+	} else {
+		None
+	};
+	let mut cache = cache_path
+		.as_ref()
+		.map(|p| load_cache(p, &active))
+		.unwrap_or_default();
+
+	let pool = rayon::ThreadPoolBuilder::new()
+		.num_threads(opt.jobs)
+		.build()
+		.expect("cannot start worker threads");
+	// errors as strings, `Box<dyn Error>` does not cross threads
+	let results: Vec<Result<(Outcome, Option<Entry>), String>> = pool.install(|| {
+		files
+			.par_iter()
+			.map(|path| {
+				let relative = relative(root, path);
+				let backup_to = backup_dir.map(|dir| dir.join(relative));
+				sanitize_file(path, cache.get(relative), opt, backup_to.as_deref())
+					.map_err(|err| err.to_string())
+			})
+			.collect()
+	});
+
+	for (path, result) in files.iter().zip(results) {
+		let relative = relative(root, path).to_path_buf();
+		match result {
+			Ok((outcome, entry)) => {
+				match outcome {
+					Outcome::Changed(summary, preview) => {
+						report.changed(path, &summary, preview.as_deref());
+						changed += 1;
+					}
+					// failed to rename
+					Outcome::Good if report.failed.contains(path) => {}
+					Outcome::Good if renamed.contains(path) => {
+						changed += 1;
+					}
+					Outcome::Good => {
+						good += 1;
+					}
+				}
+				match entry {
+					Some(entry) => cache.insert(relative, entry),
+					None => cache.remove(&relative),
+				};
+			}
 			Err(err) => {
-				error += 1;
-				println!("[File Error] {:?}", err);
+				cache.remove(&relative);
+				report.error(path, &err);
 			}
 		}
 	}
-	println!("   {}: {}", "Good".green(), good);
-	println!("{}: {}", "Changed".yellow(), changed);
-	println!("  {}: {}", "Error".red(), error);
-	if !confirmed && changed > 0 {
-		eprintln!("\n{}", "Run with --confirmed to make actual change".red());
+	// only files that passed have entries, so a dry run can record them too
+	if let Some(p) = &cache_path {
+		// files that are gone
+		cache.retain(|file, _| root.join(file).is_file());
+		if let Err(err) = save_cache(p, &active, &cache) {
+			eprintln!("[Error] cannot write the cache: {}", err);
+			eprintln!("\t=> {}", p.display());
+		}
+	}
+	let error = report.failed.len() + unreadable;
+	if opt.json {
+		let summary = json!({
+			"good": good,
+			"changed": changed,
+			"error": error,
+			"confirmed": confirmed,
+			"files": report.files,
+		});
+		println!("{}", summary);
+	} else {
+		println!("   {}: {}", "Good".green(), good);
+		println!("{}: {}", "Changed".yellow(), changed);
+		println!("  {}: {}", "Error".red(), error);
+		if !confirmed && changed > 0 {
+			eprintln!("\n{}", "Run with --confirmed to make actual change".red());
+		}
+	}
+	error == 0 && (confirmed || changed == 0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rules(fixed: &[Rule]) -> RuleSet {
+		let mut rules = RuleSet::none();
+		for &rule in fixed {
+			rules.set(rule, Mode::Fix).unwrap();
+		}
+		rules
+	}
+
+	#[test]
+	fn crlf_and_trailing_space_round_trip() {
+		let data = b"3 4  \r\n1 2\t\r\n5\r\n";
+		assert_eq!(find(data, Rule::Crlf), [5, 11, 14]);
+		assert_eq!(find(data, Rule::TrailingSpace), [3, 10]);
+		assert!(find(data, Rule::Newline).is_empty());
+
+		let rules = rules(&[Rule::Crlf, Rule::TrailingSpace]);
+		let fixed = fix(data, &rules);
+		assert_eq!(fixed, b"3 4\n1 2\n5\n");
+		assert!(violations(&fixed, &rules).is_empty());
+		assert_eq!(fix(&fixed, &rules), fixed);
+	}
+
+	#[test]
+	fn only_fixed_rules_apply() {
+		let data = b"1  2 \r\n";
+		assert_eq!(fix(data, &RuleSet::none()), data);
+		assert_eq!(fix(data, &rules(&[Rule::Crlf])), b"1  2 \n");
+		assert_eq!(fix(data, &rules(&[Rule::DoubleSpace])), b"1 2 \r\n");
+	}
+
+	#[test]
+	fn line_endings() {
+		// a lone \r and no newline at EOF
+		let data = b"1\r2";
+		assert_eq!(find(data, Rule::Newline), [1, 3]);
+		assert!(find(data, Rule::Crlf).is_empty());
+		assert_eq!(fix(data, &rules(&[Rule::Newline])), b"1\n2\n");
+	}
+
+	#[test]
+	fn offsets_count_the_bom() {
+		let data = b"\xEF\xBB\xBF1 \n";
+		assert_eq!(find(data, Rule::Bom), [0]);
+		assert_eq!(find(data, Rule::TrailingSpace), [4]);
+		assert_eq!(fix(data, &rules(&[Rule::Bom, Rule::TrailingSpace])), b"1\n");
+	}
+
+	#[test]
+	fn blank_lines_and_spaces() {
+		let data = b"  1   2\n\n \n";
+		assert_eq!(find(data, Rule::DoubleSpace), [3]);
+		assert_eq!(find(data, Rule::TrailingBlankLines), [8, 9]);
+		let rules = rules(&[Rule::DoubleSpace, Rule::TrailingBlankLines]);
+		assert_eq!(fix(data, &rules), b"  1 2\n");
+		assert_eq!(find(b" \n\n", Rule::Empty), [0]);
+	}
+
+	#[test]
+	fn confusable_characters() {
+		let data = "１\u{00A0}2\u{200B}\n".as_bytes();
+		assert_eq!(find(data, Rule::Confusable), [0, 3, 6]);
+		assert!(find(data, Rule::Utf8).is_empty());
+		assert_eq!(fix(data, &rules(&[Rule::Confusable])), b"1 2\n");
+		assert_eq!(find(b"1\xFF\n", Rule::Utf8), [1]);
+	}
+
+	#[test]
+	fn bom_is_kept_unless_fixed() {
+		let data = b"\xEF\xBB\xBF1\r\n";
+		assert_eq!(fix(data, &rules(&[Rule::Crlf])), b"\xEF\xBB\xBF1\n");
+		assert_eq!(fix(data, &rules(&[Rule::Crlf, Rule::Bom])), b"1\n");
+	}
+
+	#[test]
+	fn quick_scan() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("1.in");
+		let check = |data: &[u8], canonical: bool| {
+			std::fs::write(&path, data).unwrap();
+			assert_eq!(scan(&path).unwrap(), (content_hash(data), canonical));
+		};
+		check(b"1 2\n3\n", true);
+		for data in [&b""[..], b"\n", b"1", b"1\n\n", b"1  2\n", b"1 \n", b"\t\n"] {
+			check(data, false);
+		}
+		// a space at the end of one chunk, and another at the start of the next
+		let mut data = vec![b'1'; 2 * CHUNK];
+		data[CHUNK - 1] = b' ';
+		data.push(b'\n');
+		check(&data, true);
+		data[CHUNK] = b' ';
+		check(&data, false);
+	}
+
+	#[test]
+	fn backup_paths() {
+		let root = Path::new("data/A");
+		assert_eq!(relative(root, Path::new("data/A/1.in")), Path::new("1.in"));
+		assert_eq!(
+			relative(root, Path::new("data/A/x/1.in")),
+			Path::new("x/1.in")
+		);
+		// a single file as the root
+		let file = Path::new("data/A/1.in");
+		assert_eq!(relative(file, file), Path::new("1.in"));
 	}
 }
@@ -0,0 +1,246 @@
+use crate::generate::{self, Budget, Range};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, FLOAT, INT};
+use std::cell::RefCell;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+// psutil generate script gen.rhai -- 10 1000
+//
+// ARGS               : strings after `--`
+// rand(lo, hi)       : uniform int in [lo, hi] (float if lo, hi are floats)
+// random_array(n, lo, hi)
+// permutation(n)     : shuffled 1..=n
+// shuffle(arr)
+// tree(n)            : [[u, v], ..] on 1..=n
+// convex(n, lo, hi)  : [[x, y], ..] counter-clockwise
+// write(x), writeln(x), print(x)
+//
+// --max-bytes only checks the output, --shrink has no effect.
+//
+// Output is buffered and flushed only when the script finishes,
+// so a failing script never leaves a half-written test.
+
+fn format_value(v: &Dynamic) -> String {
+	if v.is_array() {
+		let arr = v.clone().cast::<Array>();
+		let items: Vec<String> = arr.iter().map(format_value).collect();
+		items.join(" ")
+	} else {
+		v.to_string()
+	}
+}
+
+fn pair(a: Dynamic, b: Dynamic) -> Dynamic {
+	let v: Array = vec![a, b];
+	v.into()
+}
+
+fn check_range<X: PartialOrd + std::fmt::Display>(
+	lo: X,
+	hi: X,
+) -> Result<(), Box<EvalAltResult>> {
+	if lo > hi {
+		return Err(format!("empty range: [{}, {}]", lo, hi).into());
+	}
+	Ok(())
+}
+
+// for coordinates drawn from [lo, hi)
+fn check_open_range<X: PartialOrd + std::fmt::Display>(
+	lo: X,
+	hi: X,
+) -> Result<(), Box<EvalAltResult>> {
+	if lo >= hi {
+		return Err(format!("empty range: [{}, {})", lo, hi).into());
+	}
+	Ok(())
+}
+
+fn check_size(n: INT) -> Result<usize, Box<EvalAltResult>> {
+	if n < 0 {
+		return Err(format!("negative size: {}", n).into());
+	}
+	Ok(n as usize)
+}
+
+fn build_engine(rng: Rc<RefCell<StdRng>>, out: Rc<RefCell<String>>) -> Engine {
+	let mut engine = Engine::new();
+
+	{
+		let out = out.clone();
+		engine.on_print(move |s| {
+			let mut out = out.borrow_mut();
+			out.push_str(s);
+			out.push('\n');
+		});
+	}
+	{
+		let out = out.clone();
+		engine.register_fn("write", move |v: Dynamic| {
+			out.borrow_mut().push_str(&format_value(&v));
+		});
+	}
+	{
+		let out = out.clone();
+		engine.register_fn("writeln", move |v: Dynamic| {
+			let mut out = out.borrow_mut();
+			out.push_str(&format_value(&v));
+			out.push('\n');
+		});
+	}
+	engine.register_fn("writeln", move || {
+		out.borrow_mut().push('\n');
+	});
+
+	{
+		let rng = rng.clone();
+		engine.register_fn("rand", move |lo: INT, hi: INT| -> Result<INT, Box<EvalAltResult>> {
+			check_range(lo, hi)?;
+			Ok(rng.borrow_mut().gen_range(lo..=hi))
+		});
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn(
+			"rand",
+			move |lo: FLOAT, hi: FLOAT| -> Result<FLOAT, Box<EvalAltResult>> {
+				check_range(lo, hi)?;
+				Ok(rng.borrow_mut().gen_range(lo..=hi))
+			},
+		);
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn(
+			"random_array",
+			move |n: INT, lo: INT, hi: INT| -> Result<Array, Box<EvalAltResult>> {
+				let n = check_size(n)?;
+				check_range(lo, hi)?;
+				let range = Range { low: lo, high: hi };
+				let a = generate::random_array(&mut *rng.borrow_mut(), n, &range);
+				Ok(a.into_iter().map(Dynamic::from).collect())
+			},
+		);
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn(
+			"random_array",
+			move |n: INT, lo: FLOAT, hi: FLOAT| -> Result<Array, Box<EvalAltResult>> {
+				let n = check_size(n)?;
+				check_range(lo, hi)?;
+				let range = Range { low: lo, high: hi };
+				let a = generate::random_array(&mut *rng.borrow_mut(), n, &range);
+				Ok(a.into_iter().map(Dynamic::from).collect())
+			},
+		);
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn("permutation", move |n: INT| -> Result<Array, Box<EvalAltResult>> {
+			let n = check_size(n)?;
+			let p = generate::permutation(&mut *rng.borrow_mut(), n);
+			Ok(p.into_iter().map(|v| Dynamic::from(v as INT)).collect())
+		});
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn("shuffle", move |arr: &mut Array| {
+			arr.shuffle(&mut *rng.borrow_mut());
+		});
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn("tree", move |n: INT| -> Result<Array, Box<EvalAltResult>> {
+			let n = check_size(n)?;
+			let edges = generate::tree_edges(&mut *rng.borrow_mut(), n);
+			Ok(edges
+				.into_iter()
+				.map(|(u, v)| pair(Dynamic::from(u as INT), Dynamic::from(v as INT)))
+				.collect())
+		});
+	}
+	{
+		let rng = rng.clone();
+		engine.register_fn(
+			"convex",
+			move |n: INT, lo: INT, hi: INT| -> Result<Array, Box<EvalAltResult>> {
+				let n = check_size(n)?;
+				if n < 3 {
+					return Err("convex polygon needs n >= 3".into());
+				}
+				check_open_range(lo, hi)?;
+				let range = Range { low: lo, high: hi };
+				let points = generate::convex_polygon(&mut *rng.borrow_mut(), n, &range);
+				Ok(points
+					.into_iter()
+					.map(|(x, y)| pair(Dynamic::from(x), Dynamic::from(y)))
+					.collect())
+			},
+		);
+	}
+	engine.register_fn(
+		"convex",
+		move |n: INT, lo: FLOAT, hi: FLOAT| -> Result<Array, Box<EvalAltResult>> {
+			let n = check_size(n)?;
+			if n < 3 {
+				return Err("convex polygon needs n >= 3".into());
+			}
+			check_open_range(lo, hi)?;
+			let range = Range { low: lo, high: hi };
+			let points = generate::convex_polygon(&mut *rng.borrow_mut(), n, &range);
+			Ok(points
+				.into_iter()
+				.map(|(x, y)| pair(Dynamic::from(x), Dynamic::from(y)))
+				.collect())
+		},
+	);
+
+	engine
+}
+
+pub fn run_script<P: AsRef<Path>>(
+	path: P,
+	args: Vec<&str>,
+	seed: Option<u64>,
+	budget: &Budget,
+) -> Result<(), Box<dyn Error>> {
+	let path = path.as_ref();
+	let seed = match seed {
+		Some(s) => s,
+		None => {
+			let s = rand::thread_rng().gen();
+			eprintln!("seed: {}", s);
+			s
+		}
+	};
+
+	let rng = Rc::new(RefCell::new(StdRng::seed_from_u64(seed)));
+	let out = Rc::new(RefCell::new(String::new()));
+	let engine = build_engine(rng, out.clone());
+
+	let ast = engine.compile_file(path.to_path_buf())?;
+	let mut scope = Scope::new();
+	let args: Array = args.into_iter().map(|a| Dynamic::from(a.to_string())).collect();
+	scope.push_constant("ARGS", args);
+	engine.run_ast_with_scope(&mut scope, &ast)?;
+
+	// the script decides its own sizes, so there is nothing to shrink
+	let len = out.borrow().len();
+	if let Some(max) = budget.max_bytes {
+		if len > max {
+			return Err(format!("output is {} bytes, more than --max-bytes {}", len, max).into());
+		}
+	}
+
+	let stdout = std::io::stdout();
+	let mut lock = stdout.lock();
+	lock.write_all(out.borrow().as_bytes())?;
+	lock.flush()?;
+	Ok(())
+}
@@ -0,0 +1,365 @@
+use crate::generate::Range;
+use rand::distributions::uniform::SampleUniform;
+use rand::distributions::Uniform;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::error::Error;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightDist {
+	// [low, high], both inclusive
+	Uniform,
+	// one random value for every edge
+	Equal,
+	// pairwise distinct
+	Distinct,
+	// magnitudes spread log-uniformly over [low, high]
+	LogUniform,
+	// mostly small values with a few near `high`
+	Outliers,
+	// directed only: negative weights, but every cycle sums to >= 0
+	NoNegativeCycle,
+}
+
+impl FromStr for WeightDist {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"uniform" => Ok(WeightDist::Uniform),
+			"equal" => Ok(WeightDist::Equal),
+			"distinct" => Ok(WeightDist::Distinct),
+			"log" => Ok(WeightDist::LogUniform),
+			"outliers" => Ok(WeightDist::Outliers),
+			"no-negative-cycle" => Ok(WeightDist::NoNegativeCycle),
+			_ => Err(format!("unknown weight distribution: {}", s)),
+		}
+	}
+}
+
+pub const WEIGHT_DISTS: &[&str] = &[
+	"uniform",
+	"equal",
+	"distinct",
+	"log",
+	"outliers",
+	"no-negative-cycle",
+];
+
+pub struct Weights<X> {
+	pub range: Range<X>,
+	pub dist: WeightDist,
+}
+
+/// Numeric types usable as edge weights or values.
+pub trait Weight: SampleUniform + PartialOrd + Copy + std::fmt::Display {
+	fn zero() -> Self;
+	fn add(self, other: Self) -> Self;
+	fn sub(self, other: Self) -> Self;
+
+	/// `m` pairwise distinct values in `[low, high]`, `None` if the range is too small.
+	fn distinct<R: Rng>(rng: &mut R, m: usize, low: Self, high: Self) -> Option<Vec<Self>>;
+
+	/// A value whose distance from the end of `[low, high]` nearest zero is log-uniform, on a side
+	/// of zero picked apart (by the log of the number of values there) when the range spans it.
+	fn log_uniform<R: Rng>(rng: &mut R, low: Self, high: Self) -> Self;
+
+	/// `low + (high - low) / 100`, the upper end of the bulk in `Outliers`.
+	fn percentile(low: Self, high: Self) -> Self;
+
+	/// `self / 10^digits`, rounded toward zero.
+	fn drop_digits(self, digits: u32) -> Self;
+}
+
+// whether a range with `negatives` values below zero and `non_negatives` others is drawn from
+// below zero, weighted by the log of the sizes like the magnitudes within each side
+fn negative_side<R: Rng>(rng: &mut R, negatives: f64, non_negatives: f64) -> bool {
+	let below = negatives.ln_1p();
+	let above = non_negatives.ln_1p();
+	rng.gen_range(0.0..below + above) < below
+}
+
+macro_rules! int_weight {
+	($($t:ty),*) => {$(
+		impl Weight for $t {
+			fn zero() -> Self {
+				0
+			}
+			fn add(self, other: Self) -> Self {
+				self + other
+			}
+			fn sub(self, other: Self) -> Self {
+				self - other
+			}
+			fn distinct<R: Rng>(rng: &mut R, m: usize, low: Self, high: Self) -> Option<Vec<Self>> {
+				let size = (high as i128 - low as i128 + 1) as u128;
+				if size < m as u128 {
+					return None;
+				}
+				if size > usize::MAX as u128 {
+					// practically infinite, resample collisions
+					let mut v: Vec<Self> = Vec::with_capacity(m);
+					let mut seen = std::collections::HashSet::with_capacity(m);
+					let dist = Uniform::new_inclusive(low, high);
+					while v.len() < m {
+						let x = rng.sample(&dist);
+						if seen.insert(x) {
+							v.push(x);
+						}
+					}
+					return Some(v);
+				}
+				let mut v: Vec<Self> = rand::seq::index::sample(rng, size as usize, m)
+					.into_iter()
+					.map(|i| (low as i128 + i as i128) as Self)
+					.collect();
+				v.shuffle(rng);
+				Some(v)
+			}
+			fn log_uniform<R: Rng>(rng: &mut R, low: Self, high: Self) -> Self {
+				// `low + x - 1`, `x` log-uniform in `[1, high - low + 1]`, for `0 <= low <= high`
+				let magnitude = |rng: &mut R, low: i128, high: i128| -> i128 {
+					let span = (high - low + 1) as f64;
+					let x = rng.gen_range(0.0..=span.ln()).exp().floor();
+					low + (x as i128).clamp(1, span as i128) - 1
+				};
+				let (low, high) = (low as i128, high as i128);
+				let x = if high < 0 || (low < 0 && negative_side(rng, -low as f64, (high + 1) as f64)) {
+					-magnitude(rng, (-high).max(1), -low)
+				} else {
+					magnitude(rng, low.max(0), high)
+				};
+				x as Self
+			}
+			fn percentile(low: Self, high: Self) -> Self {
+				(low as i128 + (high as i128 - low as i128) / 100) as Self
+			}
+			fn drop_digits(self, digits: u32) -> Self {
+				match 10i128.checked_pow(digits) {
+					Some(p) => (self as i128 / p) as Self,
+					None => 0,
+				}
+			}
+		}
+	)*};
+}
+
+macro_rules! float_weight {
+	($($t:ty),*) => {$(
+		impl Weight for $t {
+			fn zero() -> Self {
+				0.0
+			}
+			fn add(self, other: Self) -> Self {
+				self + other
+			}
+			fn sub(self, other: Self) -> Self {
+				self - other
+			}
+			fn distinct<R: Rng>(rng: &mut R, m: usize, low: Self, high: Self) -> Option<Vec<Self>> {
+				if low == high && m > 1 {
+					return None;
+				}
+				let dist = Uniform::new_inclusive(low, high);
+				let mut v: Vec<Self> = (0..m).map(|_| rng.sample(&dist)).collect();
+				// collisions are rare, resample them
+				for _ in 0..100 {
+					let mut sorted = v.clone();
+					sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+					sorted.dedup();
+					if sorted.len() == m {
+						return Some(v);
+					}
+					v = sorted;
+					while v.len() < m {
+						v.push(rng.sample(&dist));
+					}
+					v.shuffle(rng);
+				}
+				None
+			}
+			fn log_uniform<R: Rng>(rng: &mut R, low: Self, high: Self) -> Self {
+				let magnitude = |rng: &mut R, low: Self, high: Self| -> Self {
+					let span = high - low + 1.0;
+					let x = rng.gen_range(0.0..=span.ln()).exp();
+					(low + x - 1.0).clamp(low, high)
+				};
+				if high < 0.0 || (low < 0.0 && negative_side(rng, -low as f64, high as f64)) {
+					-magnitude(rng, (-high).max(0.0), -low)
+				} else {
+					magnitude(rng, low.max(0.0), high)
+				}
+			}
+			fn percentile(low: Self, high: Self) -> Self {
+				low + (high - low) / 100.0
+			}
+			fn drop_digits(self, digits: u32) -> Self {
+				(self / (10.0 as Self).powi(digits as i32)).trunc()
+			}
+		}
+	)*};
+}
+
+int_weight!(i8, i16, i32, i64);
+float_weight!(f32, f64);
+
+/// `m` values from `weights`, independent of any graph structure.
+pub fn sample_values<X, R>(
+	rng: &mut R,
+	m: usize,
+	weights: &Weights<X>,
+) -> Result<Vec<X>, Box<dyn Error>>
+where
+	X: Weight,
+	R: Rng,
+{
+	let Range { low, high } = weights.range;
+	if low > high {
+		return Err(format!("empty weight range: [{}, {}]", low, high).into());
+	}
+	let v = match weights.dist {
+		WeightDist::Uniform => {
+			let dist = Uniform::new_inclusive(low, high);
+			(0..m).map(|_| rng.sample(&dist)).collect()
+		}
+		WeightDist::Equal => {
+			let w = rng.gen_range(low..=high);
+			vec![w; m]
+		}
+		WeightDist::Distinct => X::distinct(rng, m, low, high).ok_or_else(|| {
+			format!(
+				"cannot pick {} distinct weights from [{}, {}]",
+				m, low, high
+			)
+		})?,
+		WeightDist::LogUniform => (0..m).map(|_| X::log_uniform(rng, low, high)).collect(),
+		WeightDist::Outliers => {
+			let cut = X::percentile(low, high);
+			let bulk = Uniform::new_inclusive(low, cut);
+			let heavy = Uniform::new_inclusive(high.sub(cut.sub(low)), high);
+			let k = std::cmp::max(1, m / 100).min(m);
+			let mut v: Vec<X> = (0..m - k).map(|_| rng.sample(&bulk)).collect();
+			v.extend((0..k).map(|_| rng.sample(&heavy)));
+			v.shuffle(rng);
+			v
+		}
+		WeightDist::NoNegativeCycle => {
+			return Err("no-negative-cycle weights need a directed graph".into());
+		}
+	};
+	Ok(v)
+}
+
+/// Weights for `edges` on `1..=n`.
+///
+/// `NoNegativeCycle` uses random potentials `p`: `w(u, v) = w0 + p[u] - p[v]` with `w0 >= 0`,
+/// so every directed cycle keeps the non-negative sum of its `w0`.
+pub fn sample_edge_weights<X, R>(
+	rng: &mut R,
+	n: usize,
+	edges: &[(usize, usize)],
+	directed: bool,
+	weights: &Weights<X>,
+) -> Result<Vec<X>, Box<dyn Error>>
+where
+	X: Weight,
+	R: Rng,
+{
+	if weights.dist != WeightDist::NoNegativeCycle {
+		return sample_values(rng, edges.len(), weights);
+	}
+	if !directed {
+		return Err("no-negative-cycle weights need a directed graph".into());
+	}
+
+	let Range { low, high } = weights.range;
+	if high < X::zero() {
+		return Err(format!(
+			"every cycle would be negative with weights in [{}, {}]",
+			low, high
+		)
+		.into());
+	}
+	// potentials in [0, k] keep p[u] - p[v] within [low, high]; -high fits where -low may not
+	let k = if low >= X::zero() {
+		X::zero()
+	} else if low > X::zero().sub(high) {
+		X::zero().sub(low)
+	} else {
+		high
+	};
+	let potential: Vec<X> = (0..n + 1).map(|_| rng.gen_range(X::zero()..=k)).collect();
+
+	Ok(edges
+		.iter()
+		.map(|&(u, v)| {
+			// w = w0 + d with w0 >= 0, drawn directly so nothing overflows
+			let d = potential[u].sub(potential[v]);
+			let lo = if low > d { low } else { d };
+			rng.gen_range(lo..=high)
+		})
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Floyd-Warshall, a negative cycle shows up as a negative dist[v][v]
+	fn has_negative_cycle(n: usize, edges: &[(usize, usize)], w: &[i64]) -> bool {
+		// far above any path of i64 weights, and twice it still fits
+		const INF: i128 = i128::MAX / 4;
+		let mut dist = vec![vec![INF; n + 1]; n + 1];
+		for (v, row) in dist.iter_mut().enumerate() {
+			row[v] = 0;
+		}
+		for (&(u, v), &w) in edges.iter().zip(w) {
+			dist[u][v] = dist[u][v].min(w as i128);
+		}
+		for k in 1..n + 1 {
+			for i in 1..n + 1 {
+				for j in 1..n + 1 {
+					dist[i][j] = dist[i][j].min(dist[i][k] + dist[k][j]);
+				}
+			}
+		}
+		(1..n + 1).any(|v| dist[v][v] < 0)
+	}
+
+	#[test]
+	fn no_negative_cycle() {
+		let mut rng = rand::thread_rng();
+		let n = 8;
+		// every ordered pair, so there are cycles of every length
+		let edges: Vec<(usize, usize)> = (1..n + 1)
+			.flat_map(|u| (1..n + 1).filter(move |&v| v != u).map(move |v| (u, v)))
+			.collect();
+		for (low, high) in [(-1000, 1000), (-10, 3), (0, 5), (i64::MIN, i64::MAX)] {
+			let weights = Weights {
+				range: Range { low, high },
+				dist: WeightDist::NoNegativeCycle,
+			};
+			for _ in 0..20 {
+				let w = sample_edge_weights(&mut rng, n, &edges, true, &weights).unwrap();
+				assert!(w.iter().all(|&x| low <= x && x <= high));
+				assert!(!has_negative_cycle(n, &edges, &w), "[{}, {}]", low, high);
+			}
+		}
+	}
+
+	#[test]
+	fn no_negative_cycle_needs_directed() {
+		let mut rng = rand::thread_rng();
+		let weights = Weights {
+			range: Range { low: -5, high: 5 },
+			dist: WeightDist::NoNegativeCycle,
+		};
+		assert!(sample_edge_weights(&mut rng, 2, &[(1, 2)], false, &weights).is_err());
+		let negative = Weights {
+			range: Range { low: -5, high: -1 },
+			dist: WeightDist::NoNegativeCycle,
+		};
+		assert!(sample_edge_weights(&mut rng, 2, &[(1, 2)], true, &negative).is_err());
+	}
+}